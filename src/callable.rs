@@ -0,0 +1,150 @@
+use crate::class::LoxInstance;
+use crate::collector::Handle;
+use crate::environment::Environment;
+use crate::error::RuntimeError;
+use crate::intern::Symbol;
+use crate::interpreter::Interpreter;
+use crate::stmt::{FunctionStatement, Stmt};
+use crate::token::Token;
+use crate::value::Value;
+use std::cell::RefCell;
+use std::rc::Rc;
+use std::time::{SystemTime, UNIX_EPOCH};
+
+pub trait LoxCallable {
+    fn arity(&self) -> usize;
+    // `paren` is the closing parenthesis of the call, errors are reported there
+    fn call(
+        &self,
+        interpreter: &mut Interpreter,
+        paren: &Token,
+        args: Vec<Value>,
+    ) -> Result<Value, RuntimeError>;
+    fn name(&self) -> String;
+    // Adds the heap values the callable keeps alive, for the cycle collector
+    fn trace(&self, _children: &mut Vec<Handle>) {}
+}
+
+// Body of a function provided by the host application
+pub type NativeFn = dyn Fn(Vec<Value>) -> Result<Value, String>;
+
+pub struct NativeFunction {
+    name: String,
+    arity: usize,
+    function: Rc<NativeFn>,
+}
+
+impl NativeFunction {
+    pub fn new(name: String, arity: usize, function: Rc<NativeFn>) -> NativeFunction {
+        NativeFunction {
+            name,
+            arity,
+            function,
+        }
+    }
+}
+
+// Seconds since the Unix epoch, defined as `clock` in every interpreter
+pub fn clock(_args: Vec<Value>) -> Result<Value, String> {
+    let now = SystemTime::now()
+        .duration_since(UNIX_EPOCH)
+        .map_err(|e| e.to_string())?;
+    Ok(Value::Number(now.as_secs_f64()))
+}
+
+impl LoxCallable for NativeFunction {
+    fn arity(&self) -> usize {
+        self.arity
+    }
+
+    fn call(
+        &self,
+        _interpreter: &mut Interpreter,
+        paren: &Token,
+        args: Vec<Value>,
+    ) -> Result<Value, RuntimeError> {
+        (self.function)(args).map_err(|message| RuntimeError::new(paren.clone(), message))
+    }
+
+    fn name(&self) -> String {
+        self.name.clone()
+    }
+}
+
+pub struct LoxFunction {
+    // Shared with the statement that declared it and with every bound copy
+    declaration: Rc<FunctionStatement>,
+    // Scope the function was declared in
+    closure: Rc<RefCell<Environment>>,
+    is_initializer: bool,
+}
+
+impl LoxFunction {
+    pub fn new(
+        declaration: Rc<FunctionStatement>,
+        closure: Rc<RefCell<Environment>>,
+        is_initializer: bool,
+    ) -> LoxFunction {
+        LoxFunction {
+            declaration,
+            closure,
+            is_initializer,
+        }
+    }
+
+    // Wrap the closure in a scope where `this` refers to the instance
+    pub fn bind(&self, this: &Symbol, instance: Rc<RefCell<LoxInstance>>) -> LoxFunction {
+        let mut env: Environment = Environment::new_enclosed(Rc::clone(&self.closure));
+        env.define(this.clone(), Value::Instance(instance));
+        LoxFunction::new(
+            Rc::clone(&self.declaration),
+            Rc::new(RefCell::new(env)),
+            self.is_initializer,
+        )
+    }
+
+    fn this(&self, interpreter: &Interpreter) -> Value {
+        self.closure
+            .borrow()
+            .get_key_at(0, interpreter.get_this_string())
+            .expect("Initializers are only called bound to an instance")
+    }
+}
+
+impl LoxCallable for LoxFunction {
+    fn arity(&self) -> usize {
+        Stmt::get_function_params(&self.declaration).len()
+    }
+
+    fn call(
+        &self,
+        interpreter: &mut Interpreter,
+        _paren: &Token,
+        args: Vec<Value>,
+    ) -> Result<Value, RuntimeError> {
+        // Each call gets its own scope holding the parameters, enclosed by the closure
+        let mut env: Environment = Environment::new_enclosed(Rc::clone(&self.closure));
+        let params: &[Token] = Stmt::get_function_params(&self.declaration);
+        for (param, arg) in params.iter().zip(args) {
+            env.define(param.get_name(), arg);
+        }
+
+        let ret = interpreter.execute_block(
+            Stmt::get_function_body(&self.declaration),
+            Rc::new(RefCell::new(env)),
+        )?;
+        // Initializers always hand back the instance, even on an early return
+        if self.is_initializer {
+            return Ok(self.this(interpreter));
+        }
+        Ok(ret.unwrap_or(Value::Nil))
+    }
+
+    fn name(&self) -> String {
+        Stmt::get_function_name(&self.declaration).get_lexeme()
+    }
+
+    fn trace(&self, children: &mut Vec<Handle>) {
+        children.push(Handle::Env(Rc::clone(&self.closure)));
+    }
+}
@@ -0,0 +1,152 @@
+use crate::callable::{LoxCallable, LoxFunction};
+use crate::collector::{self, Handle};
+use crate::error::RuntimeError;
+use crate::intern::Symbol;
+use crate::interpreter::Interpreter;
+use crate::token::Token;
+use crate::value::{Callable, Value};
+use std::cell::RefCell;
+use std::collections::HashMap;
+use std::rc::Rc;
+
+pub struct LoxClass {
+    name: String,
+    superclass: Option<Rc<LoxClass>>,
+    methods: HashMap<Symbol, Rc<LoxFunction>>,
+    // Found once here rather than on every instantiation, possibly inherited
+    initializer: Option<Rc<LoxFunction>>,
+}
+
+impl LoxClass {
+    pub fn new(
+        name: String,
+        superclass: Option<Rc<LoxClass>>,
+        methods: HashMap<Symbol, Rc<LoxFunction>>,
+        init: &Symbol,
+    ) -> LoxClass {
+        let initializer: Option<Rc<LoxFunction>> = match methods.get(init) {
+            Some(initializer) => Some(Rc::clone(initializer)),
+            None => superclass
+                .as_ref()
+                .and_then(|superclass| superclass.initializer.clone()),
+        };
+        LoxClass {
+            name,
+            superclass,
+            methods,
+            initializer,
+        }
+    }
+
+    pub fn trace(&self, children: &mut Vec<Handle>) {
+        if let Some(superclass) = &self.superclass {
+            children.push(Handle::Class(Rc::clone(superclass)));
+        }
+        for method in self.methods.values() {
+            children.push(Handle::Function(Rc::clone(method)));
+        }
+        if let Some(initializer) = &self.initializer {
+            children.push(Handle::Function(Rc::clone(initializer)));
+        }
+    }
+
+    // Look the method up on this class first, then walk up the superclass chain
+    pub fn find_method(&self, name: &Symbol) -> Option<Rc<LoxFunction>> {
+        if let Some(method) = self.methods.get(name) {
+            return Some(Rc::clone(method));
+        }
+        match &self.superclass {
+            Some(superclass) => superclass.find_method(name),
+            None => None,
+        }
+    }
+}
+
+// Calling a class creates a new instance, so the callable needs the shared handle
+impl LoxCallable for Rc<LoxClass> {
+    fn arity(&self) -> usize {
+        match &self.initializer {
+            Some(initializer) => initializer.arity(),
+            None => 0,
+        }
+    }
+
+    fn call(
+        &self,
+        interpreter: &mut Interpreter,
+        paren: &Token,
+        args: Vec<Value>,
+    ) -> Result<Value, RuntimeError> {
+        let instance = Rc::new(RefCell::new(LoxInstance::new(Rc::clone(self))));
+        if let Some(initializer) = &self.initializer {
+            initializer
+                .bind(interpreter.get_this_string(), Rc::clone(&instance))
+                .call(interpreter, paren, args)?;
+        }
+        Ok(Value::Instance(instance))
+    }
+
+    fn name(&self) -> String {
+        self.name.clone()
+    }
+}
+
+pub struct LoxInstance {
+    class: Rc<LoxClass>,
+    fields: HashMap<Symbol, Value>,
+    // Whether the cycle collector knows about this instance
+    tracked: bool,
+}
+
+impl LoxInstance {
+    pub fn new(class: Rc<LoxClass>) -> LoxInstance {
+        LoxInstance {
+            class,
+            fields: HashMap::new(),
+            tracked: false,
+        }
+    }
+
+    pub fn trace(&self, children: &mut Vec<Handle>) {
+        children.push(Handle::Class(Rc::clone(&self.class)));
+        children.extend(self.fields.values().filter_map(Handle::from_value));
+    }
+
+    // Drops the fields, once the cycle collector found the instance unreachable
+    pub fn clear(&mut self) {
+        self.fields.clear();
+    }
+
+    // Fields shadow methods, methods are bound to the instance they are accessed on
+    pub fn get(instance: &Rc<RefCell<LoxInstance>>, name: &Token, this: &Symbol) -> Option<Value> {
+        if let Some(field) = instance.borrow().fields.get(&name.get_name()) {
+            return Some(field.clone());
+        }
+
+        let method = instance.borrow().class.find_method(&name.get_name());
+        method.map(|method| {
+            Value::Callable(Callable::new(Rc::new(
+                method.bind(this, Rc::clone(instance)),
+            )))
+        })
+    }
+
+    pub fn set(instance: &Rc<RefCell<LoxInstance>>, name: &Token, val: Value) {
+        let mut fields = instance.borrow_mut();
+        // Only a field holding a heap value can lead back to the instance, after that the cycle
+        // collector has to know about it
+        let heap_value: bool = matches!(
+            val,
+            Value::Callable(_) | Value::Class(_) | Value::Instance(_)
+        );
+        if heap_value && !fields.tracked {
+            fields.tracked = true;
+            collector::track_instance(instance);
+        }
+        fields.fields.insert(name.get_name(), val);
+    }
+
+    pub fn class_name(&self) -> String {
+        self.class.name.clone()
+    }
+}
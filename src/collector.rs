@@ -0,0 +1,270 @@
+use crate::callable::{LoxCallable, LoxFunction};
+use crate::class::{LoxClass, LoxInstance};
+use crate::environment::Environment;
+use crate::value::Value;
+use std::cell::RefCell;
+use std::collections::hash_map::Entry;
+use std::collections::HashMap;
+use std::hash::{BuildHasherDefault, Hasher};
+use std::mem;
+use std::rc::{Rc, Weak};
+
+// Cycle collector for the tree-walker. Reference counting frees values as soon as nothing
+// points at them, but a closure stored in the scope it captures, or in a field of an instance
+// that scope holds, keeps itself alive. Every such cycle runs through a scope captured by a
+// function or an instance with a heap value in a field, so those are registered here. Once
+// enough were registered, everything they reach is traced, and whatever holds more
+// references than the traced objects account for is treated as a root, being used by the
+// interpreter, the host or a Rust stack frame. Environments and instances that no root
+// reaches are emptied, breaking their cycles so reference counting frees them
+
+// A strong reference to a heap value while it is being traced
+pub enum Handle {
+    Env(Rc<RefCell<Environment>>),
+    Instance(Rc<RefCell<LoxInstance>>),
+    Callable(Rc<dyn LoxCallable>),
+    Function(Rc<LoxFunction>),
+    Class(Rc<LoxClass>),
+}
+
+impl Handle {
+    pub fn from_value(val: &Value) -> Option<Handle> {
+        match val {
+            Value::Callable(callable) => Some(Handle::Callable(callable.share())),
+            Value::Class(class) => Some(Handle::Class(Rc::clone(class))),
+            Value::Instance(instance) => Some(Handle::Instance(Rc::clone(instance))),
+            _ => None,
+        }
+    }
+
+    fn address(&self) -> usize {
+        match self {
+            Handle::Env(env) => Rc::as_ptr(env) as *const () as usize,
+            Handle::Instance(instance) => Rc::as_ptr(instance) as *const () as usize,
+            Handle::Callable(callable) => Rc::as_ptr(callable) as *const () as usize,
+            Handle::Function(function) => Rc::as_ptr(function) as *const () as usize,
+            Handle::Class(class) => Rc::as_ptr(class) as *const () as usize,
+        }
+    }
+
+    fn strong_count(&self) -> usize {
+        match self {
+            Handle::Env(env) => Rc::strong_count(env),
+            Handle::Instance(instance) => Rc::strong_count(instance),
+            Handle::Callable(callable) => Rc::strong_count(callable),
+            Handle::Function(function) => Rc::strong_count(function),
+            Handle::Class(class) => Rc::strong_count(class),
+        }
+    }
+
+    // Adds every value this one references, false if it is borrowed and can't be looked at
+    fn trace(&self, children: &mut Vec<Handle>) -> bool {
+        match self {
+            Handle::Env(env) => match env.try_borrow() {
+                Ok(env) => env.trace(children),
+                Err(_) => return false,
+            },
+            Handle::Instance(instance) => match instance.try_borrow() {
+                Ok(instance) => instance.trace(children),
+                Err(_) => return false,
+            },
+            Handle::Callable(callable) => callable.trace(children),
+            Handle::Function(function) => function.trace(children),
+            Handle::Class(class) => class.trace(children),
+        }
+        true
+    }
+}
+
+// Addresses are unique already, hashing them only has to spread the bits
+#[derive(Default)]
+struct AddressHasher(u64);
+
+impl Hasher for AddressHasher {
+    fn finish(&self) -> u64 {
+        self.0
+    }
+
+    fn write(&mut self, _bytes: &[u8]) {
+        unreachable!()
+    }
+
+    fn write_usize(&mut self, address: usize) {
+        let hash: u64 = (address as u64).wrapping_mul(0x9e37_79b9_7f4a_7c15);
+        self.0 = hash ^ (hash >> 32);
+    }
+}
+
+// Every traced object is held exactly once in `objects`. Objects are traced in order, so the
+// edges of each are a contiguous run of indexes into `objects`
+#[derive(Default)]
+struct Graph {
+    objects: Vec<Handle>,
+    indexes: HashMap<usize, usize, BuildHasherDefault<AddressHasher>>,
+    edges: Vec<usize>,
+    edges_start: Vec<usize>,
+    // Number of references to each object from the other traced objects
+    internal: Vec<usize>,
+}
+
+impl Graph {
+    fn add(&mut self, handle: Handle) -> usize {
+        let index: usize = self.objects.len();
+        match self.indexes.entry(handle.address()) {
+            Entry::Occupied(entry) => *entry.get(),
+            Entry::Vacant(entry) => {
+                entry.insert(index);
+                self.objects.push(handle);
+                self.internal.push(0);
+                index
+            }
+        }
+    }
+
+    fn edges(&self, index: usize) -> &[usize] {
+        let end: usize = match self.edges_start.get(index + 1) {
+            Some(end) => *end,
+            None => self.edges.len(),
+        };
+        &self.edges[self.edges_start[index]..end]
+    }
+}
+
+struct Collector {
+    envs: Vec<Weak<RefCell<Environment>>>,
+    instances: Vec<Weak<RefCell<LoxInstance>>>,
+    // Registry size at which entries that were already freed are dropped from it
+    prune_at: usize,
+    // Number of live entries at which they are traced to find cycles
+    collect_at: usize,
+}
+
+impl Collector {
+    fn live(&self) -> usize {
+        self.envs.len() + self.instances.len()
+    }
+}
+
+const INITIAL_COLLECT_AT: usize = 16 * 1024;
+
+thread_local! {
+    static COLLECTOR: RefCell<Collector> = const {
+        RefCell::new(Collector {
+            envs: Vec::new(),
+            instances: Vec::new(),
+            prune_at: INITIAL_COLLECT_AT,
+            collect_at: INITIAL_COLLECT_AT,
+        })
+    };
+}
+
+pub fn track_env(env: &Rc<RefCell<Environment>>) {
+    COLLECTOR.with(|collector| collector.borrow_mut().envs.push(Rc::downgrade(env)));
+}
+
+pub fn track_instance(instance: &Rc<RefCell<LoxInstance>>) {
+    COLLECTOR.with(|collector| {
+        collector
+            .borrow_mut()
+            .instances
+            .push(Rc::downgrade(instance))
+    });
+}
+
+// Only called between statements, so the interpreter isn't in the middle of changing anything
+pub fn maybe_collect() {
+    let due: bool = COLLECTOR.with(|collector| {
+        let mut collector = collector.borrow_mut();
+        if collector.live() < collector.prune_at {
+            return false;
+        }
+        // Most scopes are freed when the call that created them returns, tracing is only worth
+        // it once the survivors have doubled
+        collector.envs.retain(|env| env.strong_count() > 0);
+        collector
+            .instances
+            .retain(|instance| instance.strong_count() > 0);
+        collector.prune_at = (collector.live() * 2).max(INITIAL_COLLECT_AT);
+        collector.live() >= collector.collect_at
+    });
+    if due {
+        collect();
+    }
+}
+
+fn collect() {
+    let (envs, instances) = COLLECTOR.with(|collector| {
+        let mut collector = collector.borrow_mut();
+        (
+            mem::take(&mut collector.envs),
+            mem::take(&mut collector.instances),
+        )
+    });
+
+    let mut graph: Graph = Graph::default();
+    for env in envs.iter().filter_map(Weak::upgrade) {
+        graph.add(Handle::Env(env));
+    }
+    for instance in instances.iter().filter_map(Weak::upgrade) {
+        graph.add(Handle::Instance(instance));
+    }
+    drop((envs, instances));
+    let seeds: usize = graph.objects.len();
+
+    let mut traced: bool = true;
+    let mut children: Vec<Handle> = Vec::new();
+    let mut next: usize = 0;
+    while next < graph.objects.len() {
+        if !graph.objects[next].trace(&mut children) {
+            traced = false;
+            break;
+        }
+        graph.edges_start.push(graph.edges.len());
+        for child in children.drain(..) {
+            let index: usize = graph.add(child);
+            graph.internal[index] += 1;
+            graph.edges.push(index);
+        }
+        next += 1;
+    }
+
+    let mut garbage: Vec<bool> = vec![false; graph.objects.len()];
+    if traced {
+        // References from outside the traced objects keep them alive, `objects` holds one more
+        let mut reached: Vec<bool> = vec![false; graph.objects.len()];
+        let mut stack: Vec<usize> = (0..graph.objects.len())
+            .filter(|&index| graph.objects[index].strong_count() > graph.internal[index] + 1)
+            .collect();
+        while let Some(index) = stack.pop() {
+            if !mem::replace(&mut reached[index], true) {
+                stack.extend_from_slice(graph.edges(index));
+            }
+        }
+        for (index, object) in graph.objects.iter().enumerate() {
+            if reached[index] {
+                continue;
+            }
+            match object {
+                Handle::Env(env) => env.borrow_mut().clear(),
+                Handle::Instance(instance) => instance.borrow_mut().clear(),
+                _ => {}
+            }
+            garbage[index] = true;
+        }
+    }
+
+    COLLECTOR.with(|collector| {
+        let mut collector = collector.borrow_mut();
+        for (object, garbage) in graph.objects[..seeds].iter().zip(garbage) {
+            match object {
+                Handle::Env(env) if !garbage => collector.envs.push(Rc::downgrade(env)),
+                Handle::Instance(instance) if !garbage => {
+                    collector.instances.push(Rc::downgrade(instance))
+                }
+                _ => {}
+            }
+        }
+        collector.collect_at = (collector.live() * 2).max(INITIAL_COLLECT_AT);
+        collector.prune_at = collector.collect_at;
+    });
+}
@@ -0,0 +1,246 @@
+use crate::token::{Span, Token};
+use crate::token_type::TokenType;
+
+// Which stage of the pipeline raised an error
+#[derive(Clone, Copy, Debug, PartialEq)]
+pub enum ErrorKind {
+    Scan,
+    Parse,
+    Resolve,
+    // Limits of the bytecode compiler, e.g. too many constants in one function
+    Compile,
+    Runtime,
+}
+
+// A single error, kept apart from how it ends up being shown
+#[derive(Clone, Debug)]
+pub struct Diagnostic {
+    kind: ErrorKind,
+    span: Span,
+    // Short note shown next to the underline, e.g. "at ';'"
+    label: String,
+    message: String,
+    // Raised by code an earlier `eval` defined, the span doesn't point into the current source
+    earlier: bool,
+}
+
+impl ErrorKind {
+    pub fn name(&self) -> &'static str {
+        match self {
+            ErrorKind::Scan => "scan",
+            ErrorKind::Parse => "parse",
+            ErrorKind::Resolve => "resolve",
+            ErrorKind::Compile => "compile",
+            ErrorKind::Runtime => "runtime",
+        }
+    }
+
+    pub fn code(&self) -> &'static str {
+        match self {
+            ErrorKind::Scan => "E0001",
+            ErrorKind::Parse => "E0002",
+            ErrorKind::Resolve => "E0003",
+            ErrorKind::Runtime => "E0004",
+            ErrorKind::Compile => "E0005",
+        }
+    }
+}
+
+impl Diagnostic {
+    pub fn new(kind: ErrorKind, span: Span, label: String, message: String) -> Diagnostic {
+        Diagnostic {
+            kind,
+            span,
+            label,
+            message,
+            earlier: false,
+        }
+    }
+
+    // Diagnostic pointing at a token, labelled with where it was raised
+    pub fn at_token(kind: ErrorKind, token: &Token, message: String) -> Diagnostic {
+        let label: String = if matches!(token.get_type(), TokenType::EOF) {
+            "at end".to_string()
+        } else {
+            "at '".to_string() + token.get_lexeme().as_str() + "'"
+        };
+        Diagnostic::new(kind, token.get_span(), label, message)
+    }
+
+    // Labels the diagnostic with the source text under its span, the way `at_token` would
+    pub fn with_source_label(mut self, source: &str) -> Diagnostic {
+        let offset: usize = self.span.get_offset();
+        if let Some(lexeme) = source.get(offset..offset + self.span.get_len()) {
+            self.label = "at '".to_string() + lexeme + "'";
+        }
+        self
+    }
+
+    // Marks the span as pointing into the source of an earlier `eval`, which isn't kept around,
+    // so rendering leaves out the snippet
+    pub fn from_earlier_source(mut self) -> Diagnostic {
+        self.earlier = true;
+        self
+    }
+
+    pub fn get_kind(&self) -> ErrorKind {
+        self.kind
+    }
+
+    pub fn get_span(&self) -> Span {
+        self.span
+    }
+
+    pub fn get_message(&self) -> String {
+        self.message.clone()
+    }
+
+    pub fn render(&self, file: &str, source: &str, colour: bool) -> String {
+        render(
+            self.kind,
+            file,
+            (!self.earlier).then_some(source),
+            self.span,
+            &self.label,
+            &self.message,
+            colour,
+        )
+    }
+
+    pub fn render_json(&self, file: &str, source: &str) -> String {
+        let source: &str = if self.earlier { "" } else { source };
+        render_json(self.kind, file, source, self.span, &self.message)
+    }
+}
+
+const RED: &str = "\x1b[1;31m";
+const BLUE: &str = "\x1b[1;34m";
+const BOLD: &str = "\x1b[1m";
+const RESET: &str = "\x1b[0m";
+
+// Renders an error with the offending source line and a caret underline, e.g.
+//
+// error[E0002]: Expect expression
+//  --> script.lox:2:10
+//   |
+// 2 | print 1 +;
+//   |          ^ at ';'
+//
+// Without the source the label is shown on its own
+fn render(
+    kind: ErrorKind,
+    file: &str,
+    source: Option<&str>,
+    span: Span,
+    label: &str,
+    message: &str,
+    colour: bool,
+) -> String {
+    let paint = |style: &str, text: &str| -> String {
+        if colour {
+            format!("{}{}{}", style, text, RESET)
+        } else {
+            text.to_string()
+        }
+    };
+
+    let line_no: String = span.get_line().to_string();
+    let gutter: String = " ".repeat(line_no.len());
+    let mut out: String = format!(
+        "{}{}\n{}{} {}:{}:{}\n",
+        paint(RED, &format!("error[{}]", kind.code())),
+        paint(BOLD, &format!(": {}", message)),
+        gutter,
+        paint(BLUE, "-->"),
+        file,
+        span.get_line(),
+        span.get_column()
+    );
+
+    // Split on newlines rather than using `lines` so EOF after a trailing newline still has its
+    // (empty) line. Spans made up by the interpreter have no line, only the label is shown
+    let line_index: Option<usize> = (span.get_line() as usize).checked_sub(1);
+    let line: Option<&str> =
+        source.and_then(|source| line_index.and_then(|index| source.split('\n').nth(index)));
+    let (source, text): (&str, &str) = match (source, line) {
+        (Some(source), Some(text)) => (source, text.strip_suffix('\r').unwrap_or(text)),
+        _ => {
+            if !label.is_empty() {
+                out += &format!("{} {} {}\n", gutter, paint(BLUE, "="), paint(RED, label));
+            }
+            if source.is_none() {
+                out += &format!(
+                    "{} {} in code from an earlier input\n",
+                    gutter,
+                    paint(BLUE, "=")
+                );
+            }
+            return out;
+        }
+    };
+    // Underline to the end of the span, but no further than the end of its first line
+    let prefix: usize = span.get_column() - 1;
+    let available: usize = text.chars().count().saturating_sub(prefix);
+    let width: usize = source
+        .get(span.get_offset()..span.get_offset() + span.get_len())
+        .unwrap_or("")
+        .chars()
+        .count()
+        .min(available)
+        .max(1);
+
+    // Keep tabs so the carets line up with the text above them
+    let indent: String = text
+        .chars()
+        .take(prefix)
+        .map(|c| if c == '\t' { '\t' } else { ' ' })
+        .collect();
+
+    let bar: String = paint(BLUE, "|");
+    out += &format!("{} {}\n", gutter, bar);
+    out += &format!("{} {} {}\n", paint(BLUE, &line_no), bar, text);
+    out += &format!(
+        "{} {} {}{}",
+        gutter,
+        bar,
+        indent,
+        paint(RED, &"^".repeat(width))
+    );
+    if !label.is_empty() {
+        out += &format!(" {}", paint(RED, label));
+    }
+    out + "\n"
+}
+
+// Renders an error as a single line JSON object
+fn render_json(kind: ErrorKind, file: &str, source: &str, span: Span, message: &str) -> String {
+    let lexeme: &str = source
+        .get(span.get_offset()..span.get_offset() + span.get_len())
+        .unwrap_or("");
+    format!(
+        "{{\"kind\":\"{}\",\"code\":\"{}\",\"file\":{},\"line\":{},\"column\":{},\"lexeme\":{},\"message\":{}}}",
+        kind.name(),
+        kind.code(),
+        json_string(file),
+        span.get_line(),
+        span.get_column(),
+        json_string(lexeme),
+        json_string(message)
+    )
+}
+
+fn json_string(text: &str) -> String {
+    let mut out: String = String::from("\"");
+    for c in text.chars() {
+        match c {
+            '"' => out += "\\\"",
+            '\\' => out += "\\\\",
+            '\n' => out += "\\n",
+            '\r' => out += "\\r",
+            '\t' => out += "\\t",
+            c if (c as u32) < 0x20 => out += &format!("\\u{:04x}", c as u32),
+            c => out.push(c),
+        }
+    }
+    out + "\""
+}
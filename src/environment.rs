@@ -1,66 +1,136 @@
-use std::any::Any;
+use crate::collector::{self, Handle};
+use crate::error::RuntimeError;
+use crate::intern::Symbol;
+use crate::token::Token;
+use crate::value::Value;
+use std::cell::RefCell;
 use std::collections::HashMap;
-// use crate::Rlox;
-use crate::token::{Object, Token};
-
-pub struct Value {
-    ty: Object,
-    val: Box<dyn Any>,
-}
+use std::rc::Rc;
 
 pub struct Environment {
-    values: HashMap<String, Value>,
-    // rlox: &'a mut Rlox,
+    values: HashMap<Symbol, Value>,
+    // Parent scope, None for the global scope. Shared so closures can keep it alive
+    enclosing: Option<Rc<RefCell<Environment>>>,
+    // Whether the cycle collector knows about this scope
+    tracked: bool,
 }
 
 impl Environment {
     pub fn new() -> Environment {
         Environment {
             values: HashMap::new(),
+            enclosing: None,
+            tracked: false,
         }
     }
 
-    pub fn define(&mut self, key: String, val: Box<dyn Any>, ty: Object) {
-        self.values.insert(key, Value { ty, val });
+    pub fn new_enclosed(enclosing: Rc<RefCell<Environment>>) -> Environment {
+        Environment {
+            values: HashMap::new(),
+            enclosing: Some(enclosing),
+            tracked: false,
+        }
     }
 
-    pub fn assign(&mut self, name: Token, val: Box<dyn Any>) -> Box<dyn Any> {
-        if self.values.contains_key(&name.get_lexeme()) {
-            let mut ty: Object = Object::Nil;
-            match self.values.get(&name.get_lexeme()) {
-                Some(val) => ty = val.ty.clone(),
-                None => unreachable!()
-            }
-            self.values.insert(name.get_lexeme(), Value{ty, val});
+    // A scope captured by a function can end up referencing that function, so the cycle
+    // collector has to know about it
+    pub fn track(env: &Rc<RefCell<Environment>>) {
+        let mut scope = env.borrow_mut();
+        if !scope.tracked {
+            scope.tracked = true;
+            collector::track_env(env);
+        }
+    }
+
+    pub fn trace(&self, children: &mut Vec<Handle>) {
+        children.extend(self.values.values().filter_map(Handle::from_value));
+        if let Some(enclosing) = &self.enclosing {
+            children.push(Handle::Env(Rc::clone(enclosing)));
+        }
+    }
+
+    // Drops everything the scope references, once the cycle collector found it unreachable
+    pub fn clear(&mut self) {
+        self.values.clear();
+        self.enclosing = None;
+    }
+
+    pub fn define(&mut self, key: Symbol, val: Value) {
+        self.values.insert(key, val);
+    }
+
+    pub fn assign(&mut self, name: &Token, val: Value) -> Result<Value, RuntimeError> {
+        if let Some(existing) = self.values.get_mut(&name.get_name()) {
+            *existing = val.clone();
+            return Ok(val);
+        }
+        // Not in this scope, try the enclosing one
+        if let Some(enclosing) = &self.enclosing {
+            return enclosing.borrow_mut().assign(name, val);
+        }
+        let message = format!("Undefined variable '{}'", name.get_lexeme());
+        Err(RuntimeError::new(name.clone(), message))
+    }
+
+    // Assign in the scope exactly `distance` hops out, as worked out by the resolver
+    pub fn assign_at(
+        &mut self,
+        distance: usize,
+        name: &Token,
+        val: Value,
+    ) -> Result<Value, RuntimeError> {
+        if distance == 0 {
+            return self.assign(name, val);
+        }
+        self.enclosing
+            .as_ref()
+            .expect("Resolved scope must exist")
+            .borrow_mut()
+            .assign_at(distance - 1, name, val)
+    }
+
+    // Read from the scope exactly `distance` hops out, as worked out by the resolver
+    pub fn get_at(&self, distance: usize, name: &Token) -> Result<Value, RuntimeError> {
+        if distance == 0 {
             return self.get(name);
         }
-        panic!("Undefined variable: {:?}", name.get_lexeme());
+        self.enclosing
+            .as_ref()
+            .expect("Resolved scope must exist")
+            .borrow()
+            .get_at(distance - 1, name)
     }
 
-    pub fn get(&mut self, name: Token) -> Box<dyn Any> {
-        match self.values.get(&name.get_lexeme()) {
-            Some(val) => {
-                match val.ty {
-                    Object::Number => {
-                        let value = *(&*val.val).downcast_ref::<f64>().unwrap();
-                        return Box::from(value);
-                    }
-                    Object::String => {
-                        let value = (&*val.val).downcast_ref::<String>().unwrap();
-                        return Box::new(value.clone());
-                    }
-                    Object::Nil => {
-                        return Box::from(Option::<String>::None);
-                    }
-                    Object::Bool => {
-                        // Should never store a boolean
-                        unreachable!()
-                    }
-                }
-            }
+    // Read a name the interpreter bound itself, such as `this`, from exactly `distance` hops out
+    pub fn get_key_at(&self, distance: usize, key: &Symbol) -> Option<Value> {
+        if distance == 0 {
+            return self.values.get(key).cloned();
+        }
+        self.enclosing
+            .as_ref()
+            .expect("Resolved scope must exist")
+            .borrow()
+            .get_key_at(distance - 1, key)
+    }
+
+    // Looks a name up in this scope only, for the embedding API
+    pub fn get_value(&self, name: &str) -> Option<Value> {
+        self.values.get(&Symbol::intern(name)).cloned()
+    }
+
+    pub fn names(&self) -> Vec<Symbol> {
+        self.values.keys().cloned().collect()
+    }
+
+    pub fn get(&self, name: &Token) -> Result<Value, RuntimeError> {
+        match self.values.get(&name.get_name()) {
+            Some(val) => Ok(val.clone()),
             None => {
-                // self.rlox.runtime_error(name.get_line(), format!("Undefined Variable: {:?}", name.get_lexeme()));
-                return Box::from(Option::<String>::None);
+                if let Some(enclosing) = &self.enclosing {
+                    return enclosing.borrow().get(name);
+                }
+                let message = format!("Undefined variable '{}'", name.get_lexeme());
+                Err(RuntimeError::new(name.clone(), message))
             }
         }
     }
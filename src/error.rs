@@ -0,0 +1,94 @@
+use crate::diagnostic::{Diagnostic, ErrorKind};
+use crate::token::Token;
+use std::fmt;
+
+// An error raised while running a program, points at the token that caused it
+#[derive(Debug)]
+pub struct RuntimeError {
+    token: Token,
+    message: String,
+}
+
+impl RuntimeError {
+    pub fn new(token: Token, message: String) -> RuntimeError {
+        RuntimeError { token, message }
+    }
+
+    pub fn get_token(&self) -> Token {
+        self.token.clone()
+    }
+
+    pub fn get_message(&self) -> String {
+        self.message.clone()
+    }
+}
+
+// A syntax error, the parser unwinds to the enclosing declaration and reports it there
+#[derive(Debug)]
+pub struct ParseError {
+    token: Token,
+    message: String,
+}
+
+impl ParseError {
+    pub fn new(token: Token, message: String) -> ParseError {
+        ParseError { token, message }
+    }
+
+    pub fn get_token(&self) -> Token {
+        self.token.clone()
+    }
+
+    pub fn get_message(&self) -> String {
+        self.message.clone()
+    }
+}
+
+// Why a call to `Lox::eval` failed
+#[derive(Clone, Debug)]
+pub enum LoxError {
+    // Scan, parse or resolve errors, nothing was run
+    Compile(Vec<Diagnostic>),
+    // The program started running and stopped at this error
+    Runtime(Diagnostic),
+}
+
+impl LoxError {
+    pub fn get_diagnostics(&self) -> Vec<Diagnostic> {
+        match self {
+            LoxError::Compile(diagnostics) => diagnostics.clone(),
+            LoxError::Runtime(diagnostic) => vec![diagnostic.clone()],
+        }
+    }
+}
+
+impl From<RuntimeError> for LoxError {
+    fn from(error: RuntimeError) -> LoxError {
+        LoxError::Runtime(Diagnostic::at_token(
+            ErrorKind::Runtime,
+            &error.token,
+            error.message,
+        ))
+    }
+}
+
+impl fmt::Display for LoxError {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        for (i, diagnostic) in self.get_diagnostics().iter().enumerate() {
+            if i > 0 {
+                writeln!(f)?;
+            }
+            let span = diagnostic.get_span();
+            write!(
+                f,
+                "[line {}:{}] {}",
+                span.get_line(),
+                span.get_column(),
+                diagnostic.get_message()
+            )?;
+        }
+        Ok(())
+    }
+}
+
+impl std::error::Error for LoxError {}
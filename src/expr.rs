@@ -1,5 +1,7 @@
-use crate::token::{Object, Token};
+use crate::token::{Span, Token};
 use crate::token_type::TokenType;
+use crate::value::Value;
+use std::cell::Cell;
 
 // #[path = "./token_type.rs"]
 // mod token_type;
@@ -14,16 +16,25 @@ use crate::token_type::TokenType;
 pub enum Expr {
     Assign(Box<AssignmentExpression>),
     Binary(Box<BinaryExpression>),
+    Call(Box<CallExpression>),
+    Get(Box<GetExpression>),
     Grouping(Box<GroupingExpression>),
     Literal(Box<LiteralExpression>),
+    Logical(Box<LogicalExpression>),
+    Set(Box<SetExpression>),
+    Super(Box<SuperExpression>),
+    This(Box<ThisExpression>),
     Unary(Box<UnaryExpression>),
     Variable(Box<VariableExpression>),
 }
 
 #[derive(Clone)]
 pub struct AssignmentExpression {
+    // Scopes between this reference and its binding, filled in by the resolver. None for globals
+    distance: Cell<Option<usize>>,
     name: Token,
     val: Box<Expr>,
+    span: Span,
 }
 
 #[derive(Clone)]
@@ -31,121 +42,339 @@ pub struct BinaryExpression {
     left: Box<Expr>,
     operator: Token,
     right: Box<Expr>,
+    span: Span,
+}
+
+#[derive(Clone)]
+pub struct CallExpression {
+    callee: Box<Expr>,
+    paren: Token,
+    arguments: Vec<Expr>,
+    span: Span,
+}
+
+#[derive(Clone)]
+pub struct GetExpression {
+    object: Box<Expr>,
+    name: Token,
+    span: Span,
 }
 
 #[derive(Clone)]
 pub struct GroupingExpression {
     expression: Box<Expr>,
+    span: Span,
 }
 
 #[derive(Clone)]
 pub struct LiteralExpression {
-    literal_type: Object,
-    value: Token,
+    value: Value,
+    span: Span,
+}
+
+#[derive(Clone)]
+pub struct LogicalExpression {
+    left: Box<Expr>,
+    operator: Token,
+    right: Box<Expr>,
+    span: Span,
+}
+
+#[derive(Clone)]
+pub struct SetExpression {
+    object: Box<Expr>,
+    name: Token,
+    val: Box<Expr>,
+    span: Span,
+}
+
+#[derive(Clone)]
+pub struct SuperExpression {
+    // Scopes between this reference and its binding, filled in by the resolver. None for globals
+    distance: Cell<Option<usize>>,
+    keyword: Token,
+    method: Token,
+    span: Span,
+}
+
+#[derive(Clone)]
+pub struct ThisExpression {
+    // Scopes between this reference and its binding, filled in by the resolver. None for globals
+    distance: Cell<Option<usize>>,
+    keyword: Token,
+    span: Span,
 }
 
 #[derive(Clone)]
 pub struct UnaryExpression {
     operator: Token,
     right: Box<Expr>,
+    span: Span,
 }
 
 #[derive(Clone)]
 pub struct VariableExpression {
+    // Scopes between this reference and its binding, filled in by the resolver. None for globals
+    distance: Cell<Option<usize>>,
     name: Token,
+    span: Span,
 }
 
 impl Expr {
-    pub fn get_literal_value(expr: LiteralExpression) -> String {
-        expr.value.get_lexeme()
+    // Source range the expression was parsed from
+    pub fn get_span(&self) -> Span {
+        match self {
+            Expr::Assign(expr) => expr.span,
+            Expr::Binary(expr) => expr.span,
+            Expr::Call(expr) => expr.span,
+            Expr::Get(expr) => expr.span,
+            Expr::Grouping(expr) => expr.span,
+            Expr::Literal(expr) => expr.span,
+            Expr::Logical(expr) => expr.span,
+            Expr::Set(expr) => expr.span,
+            Expr::Super(expr) => expr.span,
+            Expr::This(expr) => expr.span,
+            Expr::Unary(expr) => expr.span,
+            Expr::Variable(expr) => expr.span,
+        }
     }
 
-    pub fn get_literal_type(expr: LiteralExpression) -> Object {
-        expr.literal_type
+    pub fn get_literal_value(expr: &LiteralExpression) -> &Value {
+        &expr.value
     }
 
-    pub fn get_grouping_expr(expr: GroupingExpression) -> Expr {
-        (*expr.expression).clone()
+    pub fn get_grouping_expr(expr: &GroupingExpression) -> &Expr {
+        &expr.expression
     }
 
-    pub fn get_unary_expr(expr: UnaryExpression) -> Expr {
-        (*expr.right).clone()
+    pub fn get_unary_expr(expr: &UnaryExpression) -> &Expr {
+        &expr.right
     }
 
-    pub fn get_unary_op(expr: UnaryExpression) -> TokenType {
+    pub fn get_unary_op(expr: &UnaryExpression) -> TokenType {
         expr.operator.get_type()
     }
 
-    pub fn get_binary_left(expr: BinaryExpression) -> Expr {
-        (*expr.left).clone()
+    pub fn get_binary_left(expr: &BinaryExpression) -> &Expr {
+        &expr.left
     }
 
-    pub fn get_binary_line(expr: BinaryExpression) -> i32 {
-        expr.operator.get_line()
+    pub fn get_binary_operator(expr: &BinaryExpression) -> &Token {
+        &expr.operator
     }
 
-    pub fn get_binary_right(expr: BinaryExpression) -> Expr {
-        (*expr.right).clone()
+    pub fn get_binary_right(expr: &BinaryExpression) -> &Expr {
+        &expr.right
     }
 
-    pub fn get_binary_op(expr: BinaryExpression) -> TokenType {
+    pub fn get_binary_op(expr: &BinaryExpression) -> TokenType {
         expr.operator.get_type()
     }
 
     pub fn new_binary(left: Expr, operator: Token, right: Expr) -> Expr {
         Expr::Binary(Box::from(BinaryExpression {
+            span: Span::merge(left.get_span(), right.get_span()),
             left: Box::from(left),
             operator,
             right: Box::from(right),
         }))
     }
 
+    pub fn new_call(callee: Expr, paren: Token, arguments: Vec<Expr>) -> Expr {
+        Expr::Call(Box::from(CallExpression {
+            span: Span::merge(callee.get_span(), paren.get_span()),
+            callee: Box::from(callee),
+            paren,
+            arguments,
+        }))
+    }
+
+    pub fn get_call_callee(expr: &CallExpression) -> &Expr {
+        &expr.callee
+    }
+
+    pub fn get_call_args(expr: &CallExpression) -> &[Expr] {
+        &expr.arguments
+    }
+
+    pub fn get_call_paren(expr: &CallExpression) -> &Token {
+        &expr.paren
+    }
+
+    pub fn new_logical(left: Expr, operator: Token, right: Expr) -> Expr {
+        Expr::Logical(Box::from(LogicalExpression {
+            span: Span::merge(left.get_span(), right.get_span()),
+            left: Box::from(left),
+            operator,
+            right: Box::from(right),
+        }))
+    }
+
+    pub fn get_logical_left(expr: &LogicalExpression) -> &Expr {
+        &expr.left
+    }
+
+    pub fn get_logical_right(expr: &LogicalExpression) -> &Expr {
+        &expr.right
+    }
+
+    pub fn get_logical_op(expr: &LogicalExpression) -> TokenType {
+        expr.operator.get_type()
+    }
+
     pub fn new_unary(operator: Token, right: Expr) -> Expr {
         Expr::Unary(Box::from(UnaryExpression {
+            span: Span::merge(operator.get_span(), right.get_span()),
             operator,
             right: Box::from(right),
         }))
     }
 
-    pub fn get_unary_line(expr: UnaryExpression) -> i32 {
-        expr.operator.get_line()
+    pub fn get_unary_operator(expr: &UnaryExpression) -> &Token {
+        &expr.operator
     }
 
-    pub fn new_literal(literal_type: Object, value: Token) -> Expr {
-        Expr::Literal(Box::from(LiteralExpression {
-            literal_type,
-            value,
-        }))
+    pub fn new_literal(value: Value, span: Span) -> Expr {
+        Expr::Literal(Box::from(LiteralExpression { value, span }))
     }
 
     pub fn new_variable(name: Token) -> Expr {
-        Expr::Variable(Box::from(VariableExpression { name }))
+        Expr::Variable(Box::from(VariableExpression {
+            distance: Cell::new(None),
+            span: name.get_span(),
+            name,
+        }))
+    }
+
+    pub fn get_var_distance(expr: &VariableExpression) -> Option<usize> {
+        expr.distance.get()
     }
 
-    pub fn get_var_name(expr: VariableExpression) -> Token {
-        expr.name
+    pub fn set_var_distance(expr: &VariableExpression, distance: usize) {
+        expr.distance.set(Some(distance));
     }
 
-    pub fn new_grouping(expression: Expr) -> Expr {
+    pub fn get_var_name(expr: &VariableExpression) -> &Token {
+        &expr.name
+    }
+
+    pub fn new_grouping(expression: Expr, span: Span) -> Expr {
         Expr::Grouping(Box::from(GroupingExpression {
             expression: Box::from(expression),
+            span,
         }))
     }
 
-    pub fn get_assign_name(expr: AssignmentExpression) -> Token {
-        expr.name
+    pub fn get_assign_name(expr: &AssignmentExpression) -> &Token {
+        &expr.name
+    }
+
+    pub fn get_assign_distance(expr: &AssignmentExpression) -> Option<usize> {
+        expr.distance.get()
+    }
+
+    pub fn set_assign_distance(expr: &AssignmentExpression, distance: usize) {
+        expr.distance.set(Some(distance));
     }
 
-    pub fn get_assign_val(expr: AssignmentExpression) -> Expr {
-        (*expr.val).clone()
+    pub fn get_assign_val(expr: &AssignmentExpression) -> &Expr {
+        &expr.val
     }
 
     pub fn new_assign_expr(name: Token, val: Expr) -> Expr {
-        Expr::Assign(Box::from(AssignmentExpression{name, val: Box::from(val)}))
+        Expr::Assign(Box::from(AssignmentExpression {
+            distance: Cell::new(None),
+            span: Span::merge(name.get_span(), val.get_span()),
+            name,
+            val: Box::from(val),
+        }))
+    }
+
+    pub fn new_get(object: Expr, name: Token) -> Expr {
+        Expr::Get(Box::from(GetExpression {
+            span: Span::merge(object.get_span(), name.get_span()),
+            object: Box::from(object),
+            name,
+        }))
+    }
+
+    pub fn get_get_object(expr: &GetExpression) -> &Expr {
+        &expr.object
+    }
+
+    pub fn get_get_name(expr: &GetExpression) -> &Token {
+        &expr.name
+    }
+
+    pub fn new_set(object: Expr, name: Token, val: Expr) -> Expr {
+        Expr::Set(Box::from(SetExpression {
+            span: Span::merge(object.get_span(), val.get_span()),
+            object: Box::from(object),
+            name,
+            val: Box::from(val),
+        }))
+    }
+
+    pub fn get_set_object(expr: &SetExpression) -> &Expr {
+        &expr.object
+    }
+
+    pub fn get_set_name(expr: &SetExpression) -> &Token {
+        &expr.name
+    }
+
+    pub fn get_set_val(expr: &SetExpression) -> &Expr {
+        &expr.val
+    }
+
+    pub fn new_super(keyword: Token, method: Token) -> Expr {
+        Expr::Super(Box::from(SuperExpression {
+            distance: Cell::new(None),
+            span: Span::merge(keyword.get_span(), method.get_span()),
+            keyword,
+            method,
+        }))
+    }
+
+    pub fn get_super_distance(expr: &SuperExpression) -> Option<usize> {
+        expr.distance.get()
+    }
+
+    pub fn set_super_distance(expr: &SuperExpression, distance: usize) {
+        expr.distance.set(Some(distance));
+    }
+
+    pub fn get_super_keyword(expr: &SuperExpression) -> &Token {
+        &expr.keyword
+    }
+
+    pub fn get_super_method(expr: &SuperExpression) -> &Token {
+        &expr.method
+    }
+
+    pub fn new_this(keyword: Token) -> Expr {
+        Expr::This(Box::from(ThisExpression {
+            distance: Cell::new(None),
+            span: keyword.get_span(),
+            keyword,
+        }))
+    }
+
+    pub fn get_this_distance(expr: &ThisExpression) -> Option<usize> {
+        expr.distance.get()
+    }
+
+    pub fn set_this_distance(expr: &ThisExpression, distance: usize) {
+        expr.distance.set(Some(distance));
+    }
+
+    pub fn get_this_keyword(expr: &ThisExpression) -> &Token {
+        &expr.keyword
     }
 }
 
 // Below contents are of the AST Printer Class from the book
+#[allow(dead_code)]
 pub fn parenthesize(name: String, exprs: &[&Expr]) -> String {
     let mut out = format!("({}", name);
     for expr in exprs {
@@ -155,16 +384,37 @@ pub fn parenthesize(name: String, exprs: &[&Expr]) -> String {
     format!("{})", out)
 }
 
+#[allow(dead_code)]
 pub fn print_expr(expr: &Expr) -> String {
     let out: String = match expr {
         Expr::Binary(b) => parenthesize(b.operator.print(), &[b.left.as_ref(), b.right.as_ref()]),
+        Expr::Call(c) => {
+            let mut exprs: Vec<&Expr> = vec![c.callee.as_ref()];
+            exprs.extend(c.arguments.iter());
+            parenthesize("call".to_string(), &exprs)
+        }
+        Expr::Get(g) => parenthesize(format!(".{}", g.name.print()), &[g.object.as_ref()]),
         Expr::Grouping(g) => parenthesize("group".to_string(), &[g.expression.as_ref()]),
-        Expr::Literal(l) => format!("{}", l.value.print()),
+        Expr::Literal(l) => match &l.value {
+            Value::Nil => "nil".to_string(),
+            Value::Bool(b) => b.to_string(),
+            Value::Number(n) => n.to_string(),
+            Value::String(s) => s.to_string(),
+            // Literals are only ever constants
+            _ => unreachable!(),
+        },
+        Expr::Logical(l) => parenthesize(l.operator.print(), &[l.left.as_ref(), l.right.as_ref()]),
+        Expr::Set(s) => parenthesize(
+            format!("={}", s.name.print()),
+            &[s.object.as_ref(), s.val.as_ref()],
+        ),
+        Expr::Super(s) => format!("{}.{}", s.keyword.print(), s.method.print()),
+        Expr::This(t) => t.keyword.print(),
         Expr::Unary(u) => parenthesize(u.operator.print(), &[u.right.as_ref()]),
-        Expr::Variable(v) => format!("{} ", v.name.to_string()),
-        Expr::Assign(a) => format!("{}", a.name.to_string()),
+        Expr::Variable(v) => format!("{} ", v.name),
+        Expr::Assign(a) => a.name.to_string(),
     };
-    format!("{}", out)
+    out
 }
 
 // fn main() {
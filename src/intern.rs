@@ -0,0 +1,98 @@
+use std::cell::RefCell;
+use std::collections::HashSet;
+use std::fmt;
+use std::hash::{Hash, Hasher};
+use std::ops::Deref;
+use std::rc::Rc;
+
+// An interned string. Equal text always shares one allocation, so comparing and hashing
+// symbols only looks at the pointer
+#[derive(Clone)]
+pub struct Symbol(Rc<str>);
+
+// Every distinct string currently alive. Strings only referenced from here are dropped
+// once the table has doubled since it was last pruned
+struct Interner {
+    strings: HashSet<Rc<str>>,
+    prune_at: usize,
+}
+
+const INITIAL_PRUNE_AT: usize = 1024;
+
+thread_local! {
+    static INTERNER: RefCell<Interner> = RefCell::new(Interner {
+        strings: HashSet::new(),
+        prune_at: INITIAL_PRUNE_AT,
+    });
+}
+
+impl Interner {
+    fn intern(&mut self, text: &str) -> Rc<str> {
+        if let Some(existing) = self.strings.get(text) {
+            return Rc::clone(existing);
+        }
+        if self.strings.len() >= self.prune_at {
+            self.strings.retain(|str| Rc::strong_count(str) > 1);
+            self.prune_at = (self.strings.len() * 2).max(INITIAL_PRUNE_AT);
+        }
+        let str: Rc<str> = Rc::from(text);
+        self.strings.insert(Rc::clone(&str));
+        str
+    }
+}
+
+impl Symbol {
+    pub fn intern(text: &str) -> Symbol {
+        Symbol(INTERNER.with(|interner| interner.borrow_mut().intern(text)))
+    }
+
+    pub fn as_str(&self) -> &str {
+        &self.0
+    }
+}
+
+impl PartialEq for Symbol {
+    fn eq(&self, other: &Symbol) -> bool {
+        Rc::ptr_eq(&self.0, &other.0)
+    }
+}
+
+impl Eq for Symbol {}
+
+impl Hash for Symbol {
+    fn hash<H: Hasher>(&self, state: &mut H) {
+        (Rc::as_ptr(&self.0) as *const u8).hash(state);
+    }
+}
+
+impl Deref for Symbol {
+    type Target = str;
+
+    fn deref(&self) -> &str {
+        &self.0
+    }
+}
+
+impl fmt::Display for Symbol {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        f.write_str(&self.0)
+    }
+}
+
+impl fmt::Debug for Symbol {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        write!(f, "{:?}", &*self.0)
+    }
+}
+
+impl From<&str> for Symbol {
+    fn from(text: &str) -> Symbol {
+        Symbol::intern(text)
+    }
+}
+
+impl From<String> for Symbol {
+    fn from(text: String) -> Symbol {
+        Symbol::intern(&text)
+    }
+}
@@ -1,295 +1,547 @@
+use crate::callable::{clock, LoxCallable, LoxFunction, NativeFunction};
+use crate::class::{LoxClass, LoxInstance};
+use crate::collector;
 use crate::environment::Environment;
-use crate::expr::{AssignmentExpression, BinaryExpression, Expr, GroupingExpression, LiteralExpression, UnaryExpression, VariableExpression};
-use crate::stmt::{ExpressionStatement, PrintStatement, Stmt, VarStmt};
-use crate::token::Object;
+use crate::error::RuntimeError;
+use crate::expr::{
+    AssignmentExpression, BinaryExpression, CallExpression, Expr, GetExpression,
+    GroupingExpression, LiteralExpression, LogicalExpression, SetExpression, SuperExpression,
+    ThisExpression, UnaryExpression, VariableExpression,
+};
+use crate::intern::Symbol;
+use crate::stmt::{
+    BlockStatement, ClassStatement, ExpressionStatement, FunctionStatement, IfStatement,
+    PrintStatement, ReturnStatement, Stmt, VarStmt, WhileStatement,
+};
+use crate::token::Token;
 use crate::token_type::TokenType;
-use crate::Rlox;
-use std::any::{Any, TypeId};
+use crate::value::{Callable, Value};
+use std::cell::RefCell;
+use std::collections::HashMap;
+use std::hint::black_box;
 use std::io::{stdout, Write};
-use std::string::String;
+use std::rc::Rc;
 
-pub struct Interpreter<'a> {
-    env: Environment,
-    rlox: &'a mut Rlox,
+// Calls nested deeper than this fail with a runtime error rather than overflowing the native stack
+const MAX_CALL_DEPTH: usize = 1024;
+// Native stack nested calls may use by default, leaves room on a 2 MiB thread for the host
+const DEFAULT_MAX_STACK: usize = 1024 * 1024;
+
+pub struct Interpreter {
+    env: Rc<RefCell<Environment>>,
+    globals: Rc<RefCell<Environment>>,
+    // Where `print` writes to
+    out: Box<dyn Write>,
+    // Number of calls currently executing
+    call_depth: usize,
+    // Bytes of native stack calls may use, counted from where the program started running
+    max_stack: usize,
+    stack_base: usize,
+    this_string: Symbol,
+    super_string: Symbol,
+    init_string: Symbol,
 }
 
-impl Interpreter<'_> {
-    pub fn new(rlox: &mut Rlox) -> Interpreter {
+impl Interpreter {
+    pub fn new() -> Interpreter {
+        let globals = Rc::new(RefCell::new(Environment::new()));
+        // Handy for benchmarking scripts
+        let native = NativeFunction::new("clock".to_string(), 0, Rc::new(clock));
+        globals.borrow_mut().define(
+            Symbol::intern("clock"),
+            Value::Callable(Callable::new(Rc::new(native))),
+        );
         Interpreter {
-            rlox,
-            env: Environment::new(),
+            env: Rc::clone(&globals),
+            globals,
+            out: Box::new(stdout()),
+            call_depth: 0,
+            max_stack: DEFAULT_MAX_STACK,
+            stack_base: 0,
+            this_string: Symbol::intern("this"),
+            super_string: Symbol::intern("super"),
+            init_string: Symbol::intern("init"),
         }
     }
 
-    pub fn interpret(&mut self, stmts: Vec<Stmt>) {
-        for stmt in stmts {
-            self.execute(stmt);
-        }
+    pub fn get_this_string(&self) -> &Symbol {
+        &self.this_string
     }
 
-    fn stringify(&self, val: Box<dyn Any>) -> String {
-        let test_val = (&*val).downcast_ref::<Option<String>>();
-        return match test_val {
-            None => {
-                let type_id = (&*val).type_id();
-                if type_id == TypeId::of::<f64>() {
-                    let num: Box<f64> = val.downcast().unwrap();
-                    let mut num_str = (*num).to_string();
-                    if num_str.ends_with(".0") {
-                        num_str.truncate(num_str.len() - 2);
-                    }
-                    num_str
-                } else if type_id == TypeId::of::<String>() {
-                    let str: Box<String> = val.downcast().unwrap();
-                    *str
-                } else {
-                    unreachable!()
-                }
-            }
-            Some(_) => "nil".to_string(),
-        };
+    pub fn set_output(&mut self, out: Box<dyn Write>) {
+        self.out = out;
     }
 
-    fn visit_literal_expr(&self, expr: LiteralExpression) -> Box<dyn Any> {
-        let val: String = Expr::get_literal_value(expr.clone());
-        match Expr::get_literal_type(expr) {
-            Object::Nil => Box::from(Option::<String>::None),
-            Object::Number => Box::from(val.parse::<f64>().unwrap()),
-            Object::Bool => {
-                if matches!(val.as_str(), "false") {
-                    Box::from(false)
-                } else {
-                    Box::from(true)
-                }
-            }
-            Object::String => {
-                let new: Box<dyn Any> = Box::new(val);
-                new
-            }
-        }
+    pub fn set_max_stack(&mut self, max_stack: usize) {
+        self.max_stack = max_stack;
     }
 
-    fn visit_group_expr(&mut self, expr: GroupingExpression) -> Box<dyn Any> {
-        self.evaluate(Expr::get_grouping_expr(expr))
+    pub fn get_global(&self, name: &str) -> Option<Value> {
+        self.globals.borrow().get_value(name)
     }
 
-    fn check_number_operand(&mut self, type_id: TypeId, line: i32) {
-        if type_id == TypeId::of::<f64>() {
-            return;
-        }
-        self.rlox
-            .runtime_error(line, "Operand must be a number".to_string());
+    pub fn set_global(&mut self, name: &str, val: Value) {
+        self.globals.borrow_mut().define(Symbol::intern(name), val);
+    }
+
+    pub fn global_names(&self) -> Vec<Symbol> {
+        self.globals.borrow().names()
     }
 
-    fn check_number_operands(&mut self, left_type: TypeId, right_type: TypeId, line: i32) {
-        if left_type == TypeId::of::<f64>() && right_type == TypeId::of::<f64>() {
-            return;
+    // Runs the program, handing back the value of a trailing expression statement.
+    // A runtime error aborts the rest of the program
+    pub fn interpret(&mut self, stmts: &[Stmt]) -> Result<Value, RuntimeError> {
+        self.stack_base = stack_address();
+        let mut last: Value = Value::Nil;
+        for stmt in stmts {
+            last = match stmt {
+                Stmt::Expression(stmt) => self.visit_expr_stmt(stmt)?,
+                stmt => {
+                    self.execute(stmt)?;
+                    Value::Nil
+                }
+            };
         }
+        Ok(last)
+    }
 
-        self.rlox
-            .runtime_error(line, "Operands must be numbers".to_string());
+    fn visit_literal_expr(&self, expr: &LiteralExpression) -> Result<Value, RuntimeError> {
+        Ok(Expr::get_literal_value(expr).clone())
     }
 
-    fn visit_unary_expr(&mut self, expr: UnaryExpression) -> Box<dyn Any> {
-        let line: i32 = Expr::get_unary_line(expr.clone());
-        let val = self.evaluate(Expr::get_unary_expr(expr.clone()));
-        match Expr::get_unary_op(expr.clone()) {
-            TokenType::Minus => {
-                self.check_number_operand((&*val).type_id(), line);
-                let temp: Box<f64> = val.downcast().unwrap();
-                return Box::from(-(*temp));
-            }
-            TokenType::Bang => {
-                let right = self.evaluate(Expr::get_unary_expr(expr.clone()));
-                let test_right = (&*right).downcast_ref::<Option<String>>();
-                match test_right {
-                    None => {
-                        let actual_id = (&*right).type_id();
-                        if actual_id == TypeId::of::<bool>() {
-                            let temp: Box<bool> = right.downcast().unwrap();
-                            return Box::from(!self.is_truthy(Object::Bool, *temp));
-                        }
-                    }
-                    Some(_) => return Box::from(!self.is_truthy(Object::Nil, false)),
-                }
-                return Box::from(!self.is_truthy(Object::String, false));
-            }
+    fn visit_group_expr(&mut self, expr: &GroupingExpression) -> Result<Value, RuntimeError> {
+        self.evaluate(Expr::get_grouping_expr(expr))
+    }
+
+    fn visit_unary_expr(&mut self, expr: &UnaryExpression) -> Result<Value, RuntimeError> {
+        let val = self.evaluate(Expr::get_unary_expr(expr))?;
+        match Expr::get_unary_op(expr) {
+            TokenType::Minus => match val {
+                Value::Number(num) => Ok(Value::Number(-num)),
+                _ => Err(RuntimeError::new(
+                    Expr::get_unary_operator(expr).clone(),
+                    "Operand must be a number".to_string(),
+                )),
+            },
+            TokenType::Bang => Ok(Value::Bool(!self.is_truthy(&val))),
             // There should not be any other types of operations in Unary Expressions
             _ => unreachable!(),
         }
     }
 
-    fn visit_binary_expr(&mut self, expr: BinaryExpression) -> Box<dyn Any> {
-        let left = self.evaluate(Expr::get_binary_left(expr.clone()));
-        let right = self.evaluate(Expr::get_binary_right(expr.clone()));
-        let line: i32 = Expr::get_binary_line(expr.clone());
-        match Expr::get_binary_op(expr.clone()) {
-            TokenType::Minus => self.evaluate_numbers(left, right, TokenType::Minus, line),
-            TokenType::Slash => self.evaluate_numbers(left, right, TokenType::Slash, line),
-            TokenType::Star => self.evaluate_numbers(left, right, TokenType::Star, line),
-            TokenType::Plus => {
-                let right_type = (&*right).type_id();
-                let left_type = (&*left).type_id();
-
-                if left_type == TypeId::of::<String>() && right_type == TypeId::of::<String>() {
-                    let mut left_string: Box<String> = left.downcast().unwrap();
-                    let right_string: Box<String> = right.downcast().unwrap();
-                    left_string.push_str(right_string.as_str());
-                    return Box::new(*left_string);
-                } else if left_type == TypeId::of::<f64>() && right_type == TypeId::of::<f64>() {
-                    return self.evaluate_numbers(left, right, TokenType::Plus, line);
-                } else {
-                    self.rlox.runtime_error(
-                        line,
-                        "Operands must be either numbers or strings".to_string(),
-                    );
-                    unreachable!()
+    fn visit_binary_expr(&mut self, expr: &BinaryExpression) -> Result<Value, RuntimeError> {
+        let left = self.evaluate(Expr::get_binary_left(expr))?;
+        let right = self.evaluate(Expr::get_binary_right(expr))?;
+        let operator: &Token = Expr::get_binary_operator(expr);
+        match Expr::get_binary_op(expr) {
+            TokenType::Plus => match (left, right) {
+                (Value::String(left), Value::String(right)) => {
+                    Ok(Value::String(Symbol::from(left.to_string() + &right)))
+                }
+                (Value::Number(left), Value::Number(right)) => Ok(Value::Number(left + right)),
+                _ => Err(RuntimeError::new(
+                    operator.clone(),
+                    "Operands must be either numbers or strings".to_string(),
+                )),
+            },
+            TokenType::BangEqual => Ok(Value::Bool(left != right)),
+            TokenType::EqualEqual => Ok(Value::Bool(left == right)),
+            _ => self.evaluate_numbers(left, right, operator),
+        }
+    }
+
+    fn visit_logical_expr(&mut self, expr: &LogicalExpression) -> Result<Value, RuntimeError> {
+        let left = self.evaluate(Expr::get_logical_left(expr))?;
+
+        // Short-circuit and hand back the operand itself rather than a bool
+        match Expr::get_logical_op(expr) {
+            TokenType::Or => {
+                if self.is_truthy(&left) {
+                    return Ok(left);
                 }
             }
-            TokenType::Greater => self.evaluate_numbers(left, right, TokenType::Greater, line),
-            TokenType::GreaterEqual => {
-                self.evaluate_numbers(left, right, TokenType::GreaterEqual, line)
+            TokenType::And => {
+                if !self.is_truthy(&left) {
+                    return Ok(left);
+                }
             }
-            TokenType::Less => self.evaluate_numbers(left, right, TokenType::Less, line),
-            TokenType::LessEqual => self.evaluate_numbers(left, right, TokenType::LessEqual, line),
-            TokenType::BangEqual => Box::from(!self.is_equal(left, right)),
-            TokenType::EqualEqual => Box::from(self.is_equal(left, right)),
+            // There should not be any other types of operations in Logical Expressions
             _ => unreachable!(),
         }
+        self.evaluate(Expr::get_logical_right(expr))
     }
 
     fn evaluate_numbers(
         &mut self,
-        left: Box<dyn Any>,
-        right: Box<dyn Any>,
-        op: TokenType,
-        line: i32,
-    ) -> Box<dyn Any> {
-        self.check_number_operands((&*left).type_id(), (&*right).type_id(), line);
-        let left_num: Box<f64> = left.downcast().unwrap();
-        let right_num: Box<f64> = right.downcast().unwrap();
-
-        match op {
-            TokenType::Plus => Box::from((*left_num) + (*right_num)),
-            TokenType::Minus => Box::from((*left_num) - (*right_num)),
-            TokenType::Slash => Box::from((*left_num) / (*right_num)),
-            TokenType::Star => Box::from((*left_num) * (*right_num)),
-            TokenType::Greater => Box::from((*left_num) > (*right_num)),
-            TokenType::GreaterEqual => Box::from((*left_num) >= (*right_num)),
-            TokenType::Less => Box::from((*left_num) < (*right_num)),
-            TokenType::LessEqual => Box::from((*left_num) <= (*right_num)),
+        left: Value,
+        right: Value,
+        operator: &Token,
+    ) -> Result<Value, RuntimeError> {
+        let (left_num, right_num) = match (left, right) {
+            (Value::Number(left), Value::Number(right)) => (left, right),
+            _ => {
+                return Err(RuntimeError::new(
+                    operator.clone(),
+                    "Operands must be numbers".to_string(),
+                ))
+            }
+        };
+
+        match operator.get_type() {
+            TokenType::Minus => Ok(Value::Number(left_num - right_num)),
+            TokenType::Slash => Ok(Value::Number(left_num / right_num)),
+            TokenType::Star => Ok(Value::Number(left_num * right_num)),
+            TokenType::Greater => Ok(Value::Bool(left_num > right_num)),
+            TokenType::GreaterEqual => Ok(Value::Bool(left_num >= right_num)),
+            TokenType::Less => Ok(Value::Bool(left_num < right_num)),
+            TokenType::LessEqual => Ok(Value::Bool(left_num <= right_num)),
             _ => {
                 unreachable!()
             }
         }
     }
 
-    fn is_equal(&self, left: Box<dyn Any>, right: Box<dyn Any>) -> bool {
-        let left_test = (&*left).downcast_ref::<Option<String>>();
-        let right_test = (&*right).downcast_ref::<Option<String>>();
-        match left_test {
-            None => match right_test {
+    fn is_truthy(&self, val: &Value) -> bool {
+        match val {
+            Value::Nil => false,
+            Value::Bool(val) => *val,
+            // Every other value is truthy
+            _ => true,
+        }
+    }
+
+    fn visit_variable_expr(&mut self, expr: &VariableExpression) -> Result<Value, RuntimeError> {
+        self.lookup_variable(Expr::get_var_name(expr), Expr::get_var_distance(expr))
+    }
+
+    fn lookup_variable(
+        &self,
+        name: &Token,
+        distance: Option<usize>,
+    ) -> Result<Value, RuntimeError> {
+        match distance {
+            Some(distance) => self.env.borrow().get_at(distance, name),
+            None => self.globals.borrow().get(name),
+        }
+    }
+
+    fn visit_assign_expr(&mut self, expr: &AssignmentExpression) -> Result<Value, RuntimeError> {
+        // Evaluate the rhs
+        let val: Value = self.evaluate(Expr::get_assign_val(expr))?;
+        // Set the value
+        let name = Expr::get_assign_name(expr);
+        match Expr::get_assign_distance(expr) {
+            Some(distance) => self.env.borrow_mut().assign_at(distance, name, val),
+            None => self.globals.borrow_mut().assign(name, val),
+        }
+    }
+
+    fn visit_call_expr(&mut self, expr: &CallExpression) -> Result<Value, RuntimeError> {
+        let callee = self.evaluate(Expr::get_call_callee(expr))?;
+        let paren: &Token = Expr::get_call_paren(expr);
+
+        let mut args: Vec<Value> = Vec::new();
+        for arg in Expr::get_call_args(expr) {
+            args.push(self.evaluate(arg)?);
+        }
+
+        // Classes are callable too, calling one creates an instance
+        let function: &dyn LoxCallable = match &callee {
+            Value::Callable(function) => function.get(),
+            Value::Class(class) => class,
+            _ => {
+                return Err(RuntimeError::new(
+                    paren.clone(),
+                    "Can only call functions and classes".to_string(),
+                ))
+            }
+        };
+
+        if args.len() != function.arity() {
+            return Err(RuntimeError::new(
+                paren.clone(),
+                format!(
+                    "Expected {} arguments but got {}",
+                    function.arity(),
+                    args.len()
+                ),
+            ));
+        }
+        // Every Lox call recurses natively, so the depth alone doesn't bound the stack used
+        let stack_used: usize = self.stack_base.abs_diff(stack_address());
+        if self.call_depth == MAX_CALL_DEPTH || stack_used > self.max_stack {
+            return Err(RuntimeError::new(
+                paren.clone(),
+                "Stack overflow".to_string(),
+            ));
+        }
+        self.call_depth += 1;
+        let ret = function.call(self, paren, args);
+        self.call_depth -= 1;
+        ret
+    }
+
+    fn visit_get_expr(&mut self, expr: &GetExpression) -> Result<Value, RuntimeError> {
+        let object = self.evaluate(Expr::get_get_object(expr))?;
+        let name: &Token = Expr::get_get_name(expr);
+
+        match object {
+            Value::Instance(instance) => match LoxInstance::get(&instance, name, &self.this_string)
+            {
+                Some(val) => Ok(val),
                 None => {
-                    let right_type = (&*right).type_id();
-                    let left_type = (&*left).type_id();
-                    if left_type == TypeId::of::<f64>() && right_type == TypeId::of::<f64>() {
-                        let left_num: Box<f64> = left.downcast().unwrap();
-                        let right_num: Box<f64> = right.downcast().unwrap();
-                        return (*left_num) == (*right_num);
-                    } else if left_type == TypeId::of::<String>()
-                        && right_type == TypeId::of::<String>()
-                    {
-                        let left_str: Box<String> = left.downcast().unwrap();
-                        let right_str: Box<String> = right.downcast().unwrap();
-                        return (*left_str) == (*right_str);
-                    }
+                    let message = format!("Undefined property '{}'", name.get_lexeme());
+                    Err(RuntimeError::new(name.clone(), message))
                 }
-                Some(_) => return false,
             },
-            Some(_) => {
-                return match right_test {
-                    None => false,
-                    Some(_) => true,
-                }
-            }
+            _ => Err(RuntimeError::new(
+                name.clone(),
+                "Only instances have properties".to_string(),
+            )),
         }
+    }
+
+    fn visit_set_expr(&mut self, expr: &SetExpression) -> Result<Value, RuntimeError> {
+        let object = self.evaluate(Expr::get_set_object(expr))?;
+        let name: &Token = Expr::get_set_name(expr);
 
-        unreachable!()
+        match object {
+            Value::Instance(instance) => {
+                let val = self.evaluate(Expr::get_set_val(expr))?;
+                LoxInstance::set(&instance, name, val.clone());
+                Ok(val)
+            }
+            _ => Err(RuntimeError::new(
+                name.clone(),
+                "Only instances have fields".to_string(),
+            )),
+        }
     }
 
-    fn is_truthy(&self, object: Object, val: bool) -> bool {
-        return match object {
-            Object::Nil => false,
-            Object::Bool => val,
-            _ => true,
+    fn visit_super_expr(&mut self, expr: &SuperExpression) -> Result<Value, RuntimeError> {
+        let keyword: &Token = Expr::get_super_keyword(expr);
+        let method_name: &Token = Expr::get_super_method(expr);
+        let distance: usize =
+            Expr::get_super_distance(expr).expect("Resolver must have resolved 'super'");
+
+        let superclass = match self.env.borrow().get_at(distance, keyword)? {
+            Value::Class(class) => class,
+            // The resolver only ever binds `super` to a class
+            _ => unreachable!(),
+        };
+        // `this` always lives in the scope just inside the one binding `super`
+        let object = match self
+            .env
+            .borrow()
+            .get_key_at(distance - 1, &self.this_string)
+        {
+            Some(Value::Instance(instance)) => instance,
+            _ => unreachable!(),
         };
-    }
 
-    fn visit_variable_expr(&mut self, expr: VariableExpression) -> Box<dyn Any> {
-        self.env.get(Expr::get_var_name(expr))
+        match superclass.find_method(&method_name.get_name()) {
+            Some(method) => Ok(Value::Callable(Callable::new(Rc::new(
+                method.bind(&self.this_string, object),
+            )))),
+            None => {
+                let message = format!("Undefined property '{}'", method_name.get_lexeme());
+                Err(RuntimeError::new(method_name.clone(), message))
+            }
+        }
     }
 
-    fn visit_assign_expr(&mut self, expr: AssignmentExpression) -> Box<dyn Any> {
-        // Evaluate the rhs
-        let val: Box<dyn Any> = self.evaluate(Expr::get_assign_val(expr.clone()));
-        // Set the value
-        self.env.assign(Expr::get_assign_name(expr), val)
+    fn visit_this_expr(&mut self, expr: &ThisExpression) -> Result<Value, RuntimeError> {
+        self.lookup_variable(Expr::get_this_keyword(expr), Expr::get_this_distance(expr))
     }
 
-    fn evaluate(&mut self, expr: Expr) -> Box<dyn Any> {
+    fn evaluate(&mut self, expr: &Expr) -> Result<Value, RuntimeError> {
         match expr {
-            Expr::Literal(expr) => self.visit_literal_expr((*expr).clone()),
-            Expr::Grouping(expr) => self.visit_group_expr((*expr).clone()),
-            Expr::Unary(expr) => self.visit_unary_expr((*expr).clone()),
-            Expr::Binary(expr) => self.visit_binary_expr((*expr).clone()),
-            Expr::Variable(expr) => self.visit_variable_expr((*expr).clone()),
-            Expr::Assign(expr) => self.visit_assign_expr((*expr).clone()),
+            Expr::Literal(expr) => self.visit_literal_expr(expr),
+            Expr::Grouping(expr) => self.visit_group_expr(expr),
+            Expr::Unary(expr) => self.visit_unary_expr(expr),
+            Expr::Binary(expr) => self.visit_binary_expr(expr),
+            Expr::Call(expr) => self.visit_call_expr(expr),
+            Expr::Get(expr) => self.visit_get_expr(expr),
+            Expr::Logical(expr) => self.visit_logical_expr(expr),
+            Expr::Set(expr) => self.visit_set_expr(expr),
+            Expr::Super(expr) => self.visit_super_expr(expr),
+            Expr::This(expr) => self.visit_this_expr(expr),
+            Expr::Variable(expr) => self.visit_variable_expr(expr),
+            Expr::Assign(expr) => self.visit_assign_expr(expr),
         }
     }
 
-    fn execute(&mut self, stmt: Stmt) {
-        match stmt.clone() {
-            Stmt::Expression(stmt) => self.visit_expr_stmt((*stmt).clone()),
-            Stmt::Print(stmt) => self.visit_print_stmt((*stmt).clone()),
-            Stmt::Var(stmt) => self.visit_var_stmt((*stmt).clone()),
+    // Executes a statement, handing back a value if a return statement was hit
+    fn execute(&mut self, stmt: &Stmt) -> Result<Option<Value>, RuntimeError> {
+        collector::maybe_collect();
+        match stmt {
+            Stmt::Block(stmt) => return self.visit_block_stmt(stmt),
+            Stmt::Class(stmt) => self.visit_class_stmt(stmt)?,
+            Stmt::Expression(stmt) => {
+                self.visit_expr_stmt(stmt)?;
+            }
+            Stmt::Function(stmt) => self.visit_function_stmt(stmt),
+            Stmt::If(stmt) => return self.visit_if_stmt(stmt),
+            Stmt::Print(stmt) => self.visit_print_stmt(stmt)?,
+            Stmt::Return(stmt) => return Ok(Some(self.visit_return_stmt(stmt)?)),
+            Stmt::Var(stmt) => self.visit_var_stmt(stmt)?,
+            Stmt::While(stmt) => return self.visit_while_stmt(stmt),
         }
+        Ok(None)
     }
 
-    fn visit_expr_stmt(&mut self, stmt: ExpressionStatement) {
-        self.evaluate(Stmt::get_expr_stmt_expr(stmt));
+    fn visit_block_stmt(&mut self, stmt: &BlockStatement) -> Result<Option<Value>, RuntimeError> {
+        let env = Environment::new_enclosed(Rc::clone(&self.env));
+        self.execute_block(Stmt::get_block_stmts(stmt), Rc::new(RefCell::new(env)))
     }
 
-    fn visit_print_stmt(&mut self, stmt: PrintStatement) {
-        let out = self.evaluate(Stmt::get_print_stmt_expr(stmt));
-        let str_out = self.stringify(out);
-        println!("{:?}", str_out);
-        stdout().flush().expect("Unable to flush to stdout!");
+    pub fn execute_block(
+        &mut self,
+        stmts: &[Stmt],
+        env: Rc<RefCell<Environment>>,
+    ) -> Result<Option<Value>, RuntimeError> {
+        let previous = std::mem::replace(&mut self.env, env);
+        let mut ret: Result<Option<Value>, RuntimeError> = Ok(None);
+        for stmt in stmts {
+            ret = self.execute(stmt);
+            // Stop at the first return or error, both unwind through the enclosing blocks
+            if !matches!(ret, Ok(None)) {
+                break;
+            }
+        }
+        // Drop the block scope and restore the enclosing one
+        self.env = previous;
+        ret
     }
 
-    fn visit_var_stmt(&mut self, stmt: VarStmt) {
-        let val: Box<dyn Any>;
-        let opt: Option<Expr> = Stmt::get_var_initializer(stmt.clone());
-        match opt {
-            Some(opt) => {
-                val = self.evaluate(opt);
-            }
-            None => {
-                val = Box::from(Option::<String>::None);
+    fn visit_expr_stmt(&mut self, stmt: &ExpressionStatement) -> Result<Value, RuntimeError> {
+        self.evaluate(Stmt::get_expr_stmt_expr(stmt))
+    }
+
+    fn visit_class_stmt(&mut self, stmt: &ClassStatement) -> Result<(), RuntimeError> {
+        let name: &Token = Stmt::get_class_name(stmt);
+
+        let mut superclass: Option<Rc<LoxClass>> = None;
+        if let Some(superclass_expr) = Stmt::get_class_superclass(stmt) {
+            let token: &Token = match superclass_expr {
+                Expr::Variable(variable) => Expr::get_var_name(variable),
+                _ => name,
+            };
+            match self.evaluate(superclass_expr)? {
+                Value::Class(class) => superclass = Some(class),
+                _ => {
+                    return Err(RuntimeError::new(
+                        token.clone(),
+                        "Superclass must be a class".to_string(),
+                    ))
+                }
             }
         }
-        let ty = (&*val).downcast_ref::<Option<String>>();
-        match ty {
-            Some(_) => self.env.define(Stmt::get_var_key(stmt), val, Object::Nil),
-            None => {
-                let type_id = (&*val).type_id();
-                if type_id == TypeId::of::<f64>() {
-                    self.env
-                        .define(Stmt::get_var_key(stmt), val, Object::Number);
-                } else {
-                    self.env
-                        .define(Stmt::get_var_key(stmt), val, Object::String);
-                }
+
+        self.env.borrow_mut().define(name.get_name(), Value::Nil);
+
+        // Methods of a subclass close over a scope binding `super`
+        let enclosing = Rc::clone(&self.env);
+        if let Some(superclass) = &superclass {
+            let mut env: Environment = Environment::new_enclosed(Rc::clone(&self.env));
+            env.define(
+                self.super_string.clone(),
+                Value::Class(Rc::clone(superclass)),
+            );
+            self.env = Rc::new(RefCell::new(env));
+        }
+
+        Environment::track(&self.env);
+        let mut methods: HashMap<Symbol, Rc<LoxFunction>> = HashMap::new();
+        for method in Stmt::get_class_methods(stmt) {
+            let method_name: Symbol = Stmt::get_function_name(method).get_name();
+            let is_initializer: bool = method_name == self.init_string;
+            let function =
+                LoxFunction::new(Rc::clone(method), Rc::clone(&self.env), is_initializer);
+            methods.insert(method_name, Rc::new(function));
+        }
+        self.env = enclosing;
+
+        let class: Rc<LoxClass> = Rc::new(LoxClass::new(
+            name.get_lexeme(),
+            superclass,
+            methods,
+            &self.init_string,
+        ));
+        self.env.borrow_mut().assign(name, Value::Class(class))?;
+        Ok(())
+    }
+
+    fn visit_function_stmt(&mut self, stmt: &Rc<FunctionStatement>) {
+        let name: Symbol = Stmt::get_function_name(stmt).get_name();
+        // Capture the scope the function is declared in
+        Environment::track(&self.env);
+        let function: Rc<dyn LoxCallable> = Rc::new(LoxFunction::new(
+            Rc::clone(stmt),
+            Rc::clone(&self.env),
+            false,
+        ));
+        self.env
+            .borrow_mut()
+            .define(name, Value::Callable(Callable::new(function)));
+    }
+
+    fn visit_if_stmt(&mut self, stmt: &IfStatement) -> Result<Option<Value>, RuntimeError> {
+        let condition = self.evaluate(Stmt::get_if_condition(stmt))?;
+        if self.is_truthy(&condition) {
+            return self.execute(Stmt::get_if_then(stmt));
+        } else if let Some(else_branch) = Stmt::get_if_else(stmt) {
+            return self.execute(else_branch);
+        }
+        Ok(None)
+    }
+
+    fn visit_return_stmt(&mut self, stmt: &ReturnStatement) -> Result<Value, RuntimeError> {
+        match Stmt::get_return_value(stmt) {
+            Some(value) => self.evaluate(value),
+            None => Ok(Value::Nil),
+        }
+    }
+
+    fn visit_while_stmt(&mut self, stmt: &WhileStatement) -> Result<Option<Value>, RuntimeError> {
+        loop {
+            let condition = self.evaluate(Stmt::get_while_condition(stmt))?;
+            if !self.is_truthy(&condition) {
+                break;
+            }
+            let ret = self.execute(Stmt::get_while_body(stmt))?;
+            if ret.is_some() {
+                return Ok(ret);
             }
         }
+        Ok(None)
     }
+
+    fn visit_print_stmt(&mut self, stmt: &PrintStatement) -> Result<(), RuntimeError> {
+        let out = self.evaluate(Stmt::get_print_stmt_expr(stmt))?;
+        // A closed pipe or full disk stops the program like any other runtime error
+        writeln!(self.out, "{}", out)
+            .and_then(|()| self.out.flush())
+            .map_err(|error| {
+                RuntimeError::new(
+                    Stmt::get_print_keyword(stmt).clone(),
+                    format!("Unable to write output: {}", error),
+                )
+            })
+    }
+
+    fn visit_var_stmt(&mut self, stmt: &VarStmt) -> Result<(), RuntimeError> {
+        let val: Value = match Stmt::get_var_initializer(stmt) {
+            Some(initializer) => self.evaluate(initializer)?,
+            None => Value::Nil,
+        };
+        self.env.borrow_mut().define(Stmt::get_var_key(stmt), val);
+        Ok(())
+    }
+}
+
+// Address of a local, how far apart two of these are is how much stack was used in between
+fn stack_address() -> usize {
+    let marker: u8 = 0;
+    black_box(&marker) as *const u8 as usize
 }
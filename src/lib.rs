@@ -0,0 +1,275 @@
+mod callable;
+mod class;
+mod collector;
+mod diagnostic;
+mod environment;
+mod error;
+mod expr;
+mod intern;
+mod interpreter;
+mod parser;
+mod resolver;
+mod scanner;
+mod stmt;
+mod token;
+mod token_type;
+mod value;
+mod vm;
+
+pub use crate::diagnostic::{Diagnostic, ErrorKind};
+pub use crate::error::LoxError;
+pub use crate::intern::Symbol;
+pub use crate::token::Span;
+pub use crate::value::{Callable, Value, VmObject};
+
+use crate::callable::{NativeFn, NativeFunction};
+use crate::interpreter::Interpreter;
+use crate::parser::Parser;
+use crate::resolver::Resolver;
+use crate::scanner::Scanner;
+use crate::stmt::Stmt;
+use crate::token::Token;
+use crate::vm::Vm;
+use std::cell::RefCell;
+use std::io::{self, Write};
+use std::rc::Rc;
+
+// Collects the errors found while scanning, parsing and resolving one program
+#[derive(Debug, Default)]
+struct Rlox {
+    had_error: bool,
+    // Reject assignments to undeclared variables while resolving
+    strict: bool,
+    diagnostics: Vec<Diagnostic>,
+}
+
+impl Rlox {
+    fn report(&mut self, diagnostic: Diagnostic) {
+        self.diagnostics.push(diagnostic);
+        self.had_error = true;
+    }
+
+    fn error(&mut self, span: Span, message: &str) {
+        self.report(Diagnostic::new(
+            ErrorKind::Scan,
+            span,
+            "".to_string(),
+            message.to_string(),
+        ));
+    }
+
+    fn parse_error(&mut self, token: Token, message: String) {
+        self.report(Diagnostic::at_token(ErrorKind::Parse, &token, message));
+    }
+
+    fn resolve_error(&mut self, token: Token, message: String) {
+        self.report(Diagnostic::at_token(ErrorKind::Resolve, &token, message));
+    }
+
+    fn compile_error(&mut self, span: Span, message: &str) {
+        self.report(Diagnostic::new(
+            ErrorKind::Compile,
+            span,
+            "".to_string(),
+            message.to_string(),
+        ));
+    }
+}
+
+// Which engine runs programs
+#[derive(Clone, Copy, Debug, Default, PartialEq)]
+pub enum Backend {
+    // Walks the syntax tree directly
+    #[default]
+    Tree,
+    // Compiles to bytecode and runs it on a stack machine
+    Vm,
+}
+
+// Lets both backends print to the same sink
+struct SharedOutput(Rc<RefCell<Box<dyn Write>>>);
+
+impl Write for SharedOutput {
+    fn write(&mut self, buf: &[u8]) -> io::Result<usize> {
+        self.0.borrow_mut().write(buf)
+    }
+
+    fn flush(&mut self) -> io::Result<()> {
+        self.0.borrow_mut().flush()
+    }
+}
+
+// An embeddable Lox interpreter. Globals persist from one `eval` to the next
+pub struct Lox {
+    backend: Backend,
+    interpreter: Interpreter,
+    vm: Vm,
+    strict: bool,
+    // Failed evals also write their errors here, one per line
+    err: Option<Box<dyn Write>>,
+}
+
+impl Default for Lox {
+    fn default() -> Lox {
+        Lox::new()
+    }
+}
+
+impl Lox {
+    pub fn new() -> Lox {
+        Lox {
+            backend: Backend::Tree,
+            interpreter: Interpreter::new(),
+            vm: Vm::new(),
+            strict: false,
+            err: None,
+        }
+    }
+
+    // Opt in to rejecting assignments to undeclared variables
+    pub fn set_strict(&mut self, strict: bool) {
+        self.strict = strict;
+    }
+
+    // Globals are kept per backend, so pick one before running anything
+    pub fn set_backend(&mut self, backend: Backend) {
+        self.backend = backend;
+    }
+
+    // The tree-walker recurses on the native stack for every Lox call. Once the calls of one
+    // `eval` use more than `bytes` of it they fail with a stack overflow error. The default of
+    // 1 MiB is safe on a 2 MiB thread, raise it on bigger threads to allow deeper recursion
+    pub fn set_max_stack(&mut self, bytes: usize) {
+        self.interpreter.set_max_stack(bytes);
+    }
+
+    // Print bytecode after compiling it, only used by the VM backend
+    pub fn set_disassemble(&mut self, disassemble: bool) {
+        self.vm.set_disassemble(disassemble);
+    }
+
+    // Print the stack before every instruction, only used by the VM backend
+    pub fn set_trace(&mut self, trace: bool) {
+        self.vm.set_trace(trace);
+    }
+
+    // Collect garbage on every allocation, only used by the VM backend
+    pub fn set_stress_gc(&mut self, stress_gc: bool) {
+        self.vm.set_stress_gc(stress_gc);
+    }
+
+    // Sends `print` output somewhere other than stdout
+    pub fn set_output(&mut self, out: Box<dyn Write>) {
+        let out = Rc::new(RefCell::new(out));
+        self.interpreter
+            .set_output(Box::new(SharedOutput(Rc::clone(&out))));
+        self.vm.set_output(Box::new(SharedOutput(out)));
+    }
+
+    // Also write errors to `err` as they are returned, by default they are only returned
+    pub fn set_error_output(&mut self, err: Box<dyn Write>) {
+        self.err = Some(err);
+    }
+
+    // Runs a program, handing back the value of its last statement if that is an expression
+    pub fn eval(&mut self, source: &str) -> Result<Value, LoxError> {
+        let result = self.run(source);
+        if let (Err(error), Some(err)) = (&result, self.err.as_mut()) {
+            // The error is returned either way, so a sink that stopped accepting it isn't fatal
+            let _ = writeln!(err, "{}", error).and_then(|()| err.flush());
+        }
+        result
+    }
+
+    fn run(&mut self, source: &str) -> Result<Value, LoxError> {
+        let mut rlox: Rlox = Rlox {
+            strict: self.strict,
+            ..Default::default()
+        };
+
+        let mut scanner = Scanner::new(source.to_string(), &mut rlox);
+        let tokens: Vec<Token> = scanner.scan_tokens();
+        let source_id: u32 = scanner.get_source_id();
+
+        let mut parser: Parser = Parser::new(tokens, &mut rlox);
+        let stmts: Vec<Stmt> = parser.parse();
+        // Don't run code with syntax errors
+        if rlox.had_error {
+            return Err(LoxError::Compile(rlox.diagnostics));
+        }
+
+        let mut resolver: Resolver = Resolver::new(&mut rlox);
+        resolver.declare_globals(match self.backend {
+            Backend::Tree => self.interpreter.global_names(),
+            Backend::Vm => self.vm.global_names(),
+        });
+        resolver.resolve(&stmts);
+        // Stop if there was a resolution error
+        if rlox.had_error {
+            return Err(LoxError::Compile(rlox.diagnostics));
+        }
+
+        let result: Result<Value, LoxError> = match self.backend {
+            Backend::Tree => self.interpreter.interpret(&stmts).map_err(LoxError::from),
+            Backend::Vm => {
+                let function = self.vm.compile(&mut rlox, &stmts)?;
+                if rlox.had_error {
+                    return Err(LoxError::Compile(rlox.diagnostics));
+                }
+                self.vm.interpret(function)
+            }
+        };
+        result.map_err(|error| match error {
+            // Functions from an earlier eval fail with spans into that eval's source, spans made
+            // up by the VM have no source at all
+            LoxError::Runtime(diagnostic)
+                if ![0, source_id].contains(&diagnostic.get_span().get_source()) =>
+            {
+                LoxError::Runtime(diagnostic.from_earlier_source())
+            }
+            // Bytecode only keeps spans, recover the label from the source
+            LoxError::Runtime(diagnostic) if self.backend == Backend::Vm => {
+                LoxError::Runtime(diagnostic.with_source_label(source))
+            }
+            error => error,
+        })
+    }
+
+    // The VM hands back its functions, classes and instances as `Value::VmObject`
+    pub fn get_global(&self, name: &str) -> Option<Value> {
+        match self.backend {
+            Backend::Tree => self.interpreter.get_global(name),
+            Backend::Vm => self.vm.get_global(name),
+        }
+    }
+
+    // Sets the global in both backends. Fails if the active backend can't hold the value, the
+    // VM has no counterpart for the tree-walker's functions, classes and instances, and neither
+    // backend takes a `Value::VmObject` back
+    pub fn set_global(&mut self, name: &str, val: Value) -> Result<(), String> {
+        if let Value::VmObject(_) = val {
+            return Err(format!("Scripts can't be handed the VM object {}", val));
+        }
+        // The VM leaves its globals alone when it rejects a value, so try it first and only
+        // touch the tree-walker once nothing can fail anymore
+        let vm_result = self.vm.set_global(name, val.clone());
+        if let (Backend::Vm, Err(error)) = (self.backend, vm_result) {
+            return Err(error);
+        }
+        self.interpreter.set_global(name, val);
+        Ok(())
+    }
+
+    // Exposes a Rust closure to scripts as a global function. Calls with the wrong number
+    // of arguments are rejected before it runs, an `Err` becomes a Lox runtime error
+    pub fn define_native<F>(&mut self, name: &str, arity: usize, function: F)
+    where
+        F: Fn(Vec<Value>) -> Result<Value, String> + 'static,
+    {
+        let function: Rc<NativeFn> = Rc::new(function);
+        self.vm.define_native(name, arity, Rc::clone(&function));
+        let native = NativeFunction::new(name.to_string(), arity, function);
+        self.interpreter
+            .set_global(name, Value::Callable(Callable::new(Rc::new(native))));
+    }
+}
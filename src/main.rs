@@ -1,47 +1,49 @@
-mod environment;
-mod expr;
-mod interpreter;
-mod parser;
-pub mod scanner;
-mod stmt;
-mod token;
-mod token_type;
-
-use scanner::Scanner;
-
-use crate::interpreter::Interpreter;
-use crate::parser::Parser;
-use crate::stmt::Stmt;
-use crate::token::Token;
-use crate::token_type::TokenType;
+use rlox::{Backend, Lox, LoxError};
 use std::env;
 use std::fs;
-use std::io::{stdin, stdout, Write};
+use std::io::{stderr, stdin, stdout, IsTerminal, Write};
 use std::process::ExitCode;
+use std::thread;
 
-#[derive(Debug)]
-struct Rlox {
-    had_error: bool,
-    had_runtime_error: bool,
+// Scripts run on a thread with this much stack, the tree-walker recurses natively for every
+// Lox call and the main thread's stack runs out well before the call depth limit in debug builds
+const STACK_SIZE: usize = 256 * 1024 * 1024;
+// Stack the tree-walker's calls may use, the rest is left for the front end
+const MAX_STACK: usize = STACK_SIZE / 2;
+
+const USAGE: &str = "Usage: rlox [--strict] [--backend=tree|vm] [--disassemble] [--trace] \
+                     [--stress-gc] [--error-format=human|json] [script]";
+
+// How errors are written to stderr
+#[derive(Clone, Copy, Debug, Default, PartialEq)]
+enum ErrorFormat {
+    #[default]
+    Human,
+    // One JSON object per line, for editors and CI
+    Json,
 }
 
-impl Default for Rlox {
-    fn default() -> Rlox {
-        Rlox {
-            had_error: false,
-            had_runtime_error: false,
-        }
-    }
+// Command line front end, a thin wrapper over the library
+#[derive(Default)]
+struct Rlox {
+    lox: Lox,
+    error_format: ErrorFormat,
 }
 
 impl Rlox {
-    fn run_file(&mut self, filepath: String) {
-        let file = fs::read_to_string(filepath).expect("Unable to read file!");
-        self.run(file);
-        if self.had_error {
-            let _ = ExitCode::from(65);
-        } else if self.had_runtime_error {
-            let _ = ExitCode::from(70);
+    fn run_file(&mut self, filepath: String) -> ExitCode {
+        let source = match fs::read_to_string(&filepath) {
+            Ok(source) => source,
+            Err(e) => {
+                eprintln!("Unable to read file '{}': {}", filepath, e);
+                return ExitCode::from(66);
+            }
+        };
+        match self.run(&filepath, &source) {
+            Ok(()) => ExitCode::SUCCESS,
+            // Exit statuses follow the sysexits convention
+            Err(LoxError::Compile(_)) => ExitCode::from(65),
+            Err(LoxError::Runtime(_)) => ExitCode::from(70),
         }
     }
 
@@ -51,72 +53,97 @@ impl Rlox {
         loop {
             input.clear();
             print!("> ");
-            stdout().flush().expect("Unable to flush to stdout!");
+            // Nobody is reading the prompt anymore
+            if stdout().flush().is_err() {
+                break;
+            }
             match stdin().read_line(&mut input) {
                 Ok(0) => break,
-                Ok(_) => self.run(input.clone()),
+                Ok(_) => {
+                    // Errors are already reported, carry on with the next line
+                    let _ = self.run("<stdin>", &input.clone());
+                }
                 Err(e) => panic!("{}", e),
             }
-            self.had_error = false;
-            self.had_runtime_error = false;
         }
     }
 
-    fn report(&mut self, line: i32, loc: String, message: &str) {
-        eprintln!("[line {}] {}: {}", line, loc, message);
-        self.had_error = true;
-    }
-
-    fn error(&mut self, line: i32, message: &str) {
-        self.report(line, "".to_string(), message);
-    }
-
-    fn parse_error(&mut self, token: Token, message: String) {
-        if matches!(token.get_type(), TokenType::EOF) {
-            self.report(token.get_line(), " at end".to_string(), message.as_str());
-        } else {
-            self.report(
-                token.get_line(),
-                "at '".to_string() + token.get_lexeme().as_str() + "'",
-                message.as_str(),
-            );
+    fn run(&mut self, file: &str, source: &str) -> Result<(), LoxError> {
+        if let Err(error) = self.lox.eval(source) {
+            self.report(file, source, &error);
+            return Err(error);
         }
+        Ok(())
     }
 
-    fn runtime_error(&mut self, line: i32, message: String) {
-        eprintln!("[line {}]: {}", line, message);
-        self.had_runtime_error = true;
-    }
-
-    fn run(&mut self, source: String) {
-        let mut scanner = Scanner::new(source, self);
-        let tokens: Vec<Token> = scanner.scan_tokens();
-
-        let mut parser: Parser = Parser::new(tokens, self);
-        let stmts: Vec<Stmt> = parser.parse();
-        let mut interpreter: Interpreter = Interpreter::new(self);
-        interpreter.interpret(stmts);
-
-        // If error, return
-        if self.had_error || self.had_runtime_error {
-            return;
+    fn report(&self, file: &str, source: &str, error: &LoxError) {
+        let colour: bool = stderr().is_terminal();
+        for diagnostic in error.get_diagnostics() {
+            let text: String = match self.error_format {
+                ErrorFormat::Human => diagnostic.render(file, source, colour),
+                ErrorFormat::Json => format!("{}\n", diagnostic.render_json(file, source)),
+            };
+            // The exit status still reports the failure if stderr is closed too
+            let _ = stderr().write_all(text.as_bytes());
         }
     }
 }
 
 fn main() -> ExitCode {
-    let args: Vec<String> = env::args().collect();
+    thread::Builder::new()
+        .stack_size(STACK_SIZE)
+        .spawn(run_cli)
+        .expect("Unable to start the interpreter thread!")
+        .join()
+        .unwrap_or_else(|panic| std::panic::resume_unwind(panic))
+}
+
+fn run_cli() -> ExitCode {
     let mut rlox: Rlox = Rlox {
         ..Default::default()
     };
-    if args.len() > 2 {
-        println!("Usage: rlox [script]");
-        ExitCode::from(64)
-    } else if args.len() == 2 {
-        rlox.run_file(args[1].clone());
-        ExitCode::SUCCESS
-    } else {
-        rlox.run_prompt();
-        ExitCode::SUCCESS
+    rlox.lox.set_max_stack(MAX_STACK);
+    let mut script: Option<String> = None;
+    let mut backend: Backend = Backend::Tree;
+    let mut debugging: bool = false;
+    for arg in env::args().skip(1) {
+        match arg.as_str() {
+            "--strict" => rlox.lox.set_strict(true),
+            "--backend=tree" => backend = Backend::Tree,
+            "--backend=vm" => backend = Backend::Vm,
+            "--disassemble" => {
+                rlox.lox.set_disassemble(true);
+                debugging = true;
+            }
+            "--trace" => {
+                rlox.lox.set_trace(true);
+                debugging = true;
+            }
+            "--stress-gc" => {
+                rlox.lox.set_stress_gc(true);
+                debugging = true;
+            }
+            "--error-format=human" => rlox.error_format = ErrorFormat::Human,
+            "--error-format=json" => rlox.error_format = ErrorFormat::Json,
+            _ if arg.starts_with("--") || script.is_some() => {
+                eprintln!("{}", USAGE);
+                return ExitCode::from(64);
+            }
+            _ => script = Some(arg),
+        }
+    }
+    // The tree-walker has no bytecode or collector to debug
+    if debugging && backend != Backend::Vm {
+        eprintln!("--disassemble, --trace and --stress-gc need --backend=vm");
+        return ExitCode::from(64);
+    }
+    rlox.lox.set_backend(backend);
+
+    match script {
+        Some(script) => rlox.run_file(script),
+        None => {
+            rlox.run_prompt();
+            ExitCode::SUCCESS
+        }
     }
 }
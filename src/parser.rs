@@ -1,8 +1,12 @@
+use crate::error::ParseError;
 use crate::expr::Expr;
-use crate::stmt::Stmt;
-use crate::token::{Object, Token};
+use crate::intern::Symbol;
+use crate::stmt::{FunctionStatement, Stmt};
+use crate::token::{Object, Span, Token};
 use crate::token_type::TokenType;
+use crate::value::Value;
 use crate::Rlox;
+use std::rc::Rc;
 
 pub struct Parser<'a> {
     tokens: Vec<Token>,
@@ -11,7 +15,7 @@ pub struct Parser<'a> {
 }
 
 impl Parser<'_> {
-    pub fn new(tokens: Vec<Token>, rlox: &mut Rlox) -> Parser {
+    pub fn new(tokens: Vec<Token>, rlox: &mut Rlox) -> Parser<'_> {
         Parser {
             tokens,
             current: 0,
@@ -22,94 +26,368 @@ impl Parser<'_> {
     pub fn parse(&mut self) -> Vec<Stmt> {
         let mut stmts: Vec<Stmt> = Vec::new();
         while !self.is_at_end() {
-            stmts.push(self.declaration());
+            if let Some(stmt) = self.declaration() {
+                stmts.push(stmt);
+            }
         }
         stmts
     }
 
-    fn declaration(&mut self) -> Stmt {
+    // Syntax errors are caught here so parsing can carry on with the next statement
+    fn declaration(&mut self) -> Option<Stmt> {
+        match self.declaration_inner() {
+            Ok(stmt) => Some(stmt),
+            Err(error) => {
+                self.rlox
+                    .parse_error(error.get_token(), error.get_message());
+                self.synchronise();
+                None
+            }
+        }
+    }
+
+    fn declaration_inner(&mut self) -> Result<Stmt, ParseError> {
+        if self.match_token(&[&TokenType::Class]) {
+            return self.class_declaration();
+        }
+        if self.match_token(&[&TokenType::Fun]) {
+            return Ok(Stmt::Function(Rc::new(self.function("function")?)));
+        }
         if self.match_token(&[&TokenType::Var]) {
             return self.var_declaration();
         }
-        return self.statement();
+        self.statement()
+    }
+
+    fn class_declaration(&mut self) -> Result<Stmt, ParseError> {
+        let start: Span = self.previous().get_span();
+        let name: Token = self.consume(TokenType::Identifier, "Expect class name".to_string())?;
+
+        let mut superclass: Option<Expr> = None;
+        if self.match_token(&[&TokenType::Less]) {
+            let superclass_name: Token =
+                self.consume(TokenType::Identifier, "Expect superclass name".to_string())?;
+            superclass = Some(Expr::new_variable(superclass_name));
+        }
+
+        self.consume(
+            TokenType::LeftBrace,
+            "Expect '{' before class body".to_string(),
+        )?;
+
+        let mut methods: Vec<Rc<FunctionStatement>> = Vec::new();
+        while !self.check(TokenType::RightBrace) && !self.is_at_end() {
+            methods.push(Rc::new(self.function("method")?));
+        }
+        self.consume(
+            TokenType::RightBrace,
+            "Expect '}' after class body".to_string(),
+        )?;
+        Ok(Stmt::Class(Box::from(Stmt::new_class_stmt(
+            name,
+            superclass,
+            methods,
+            self.span_from(start),
+        ))))
+    }
+
+    fn function(&mut self, kind: &str) -> Result<FunctionStatement, ParseError> {
+        let name: Token = self.consume(TokenType::Identifier, format!("Expect {} name", kind))?;
+        self.consume(
+            TokenType::LeftParen,
+            format!("Expect '(' after {} name", kind),
+        )?;
+
+        let mut params: Vec<Token> = Vec::new();
+        if !self.check(TokenType::RightParen) {
+            loop {
+                // Reported but not thrown, the parser is still in a sane state
+                if params.len() >= 255 {
+                    self.rlox.parse_error(
+                        self.peek(),
+                        "Can't have more than 255 parameters".to_string(),
+                    );
+                }
+                params.push(
+                    self.consume(TokenType::Identifier, "Expect parameter name".to_string())?,
+                );
+                if !self.match_token(&[&TokenType::Comma]) {
+                    break;
+                }
+            }
+        }
+        self.consume(
+            TokenType::RightParen,
+            "Expect ')' after parameters".to_string(),
+        )?;
+
+        self.consume(
+            TokenType::LeftBrace,
+            format!("Expect '{{' before {} body", kind),
+        )?;
+        let body: Vec<Stmt> = self.block()?;
+        let span: Span = self.span_from(name.get_span());
+        Ok(Stmt::new_function_stmt(name, params, body, span))
     }
 
-    fn var_declaration(&mut self) -> Stmt {
-        self.consume(TokenType::Identifier, "Expect variable name".to_string());
-        let var = self.previous();
+    fn var_declaration(&mut self) -> Result<Stmt, ParseError> {
+        let start: Span = self.previous().get_span();
+        let var: Token = self.consume(TokenType::Identifier, "Expect variable name".to_string())?;
         let mut initializer: Option<Expr> = None;
         if self.match_token(&[&TokenType::Equal]) {
-            initializer = Some(self.expression());
+            initializer = Some(self.expression()?);
         }
 
         self.consume(
             TokenType::SemiColon,
             "Expect ';' after variable declaration".to_string(),
-        );
-        Stmt::Var(Box::from(Stmt::new_var_stmt(var, initializer)))
+        )?;
+        Ok(Stmt::Var(Box::from(Stmt::new_var_stmt(
+            var,
+            initializer,
+            self.span_from(start),
+        ))))
     }
 
-    fn statement(&mut self) -> Stmt {
+    fn statement(&mut self) -> Result<Stmt, ParseError> {
+        if self.match_token(&[&TokenType::For]) {
+            return self.for_statement();
+        }
+        if self.match_token(&[&TokenType::If]) {
+            return self.if_statement();
+        }
         if self.match_token(&[&TokenType::Print]) {
             return self.print_statement();
         }
-        return self.expression_statement();
+        if self.match_token(&[&TokenType::Return]) {
+            return self.return_statement();
+        }
+        if self.match_token(&[&TokenType::While]) {
+            return self.while_statement();
+        }
+        if self.match_token(&[&TokenType::LeftBrace]) {
+            let start: Span = self.previous().get_span();
+            let stmts: Vec<Stmt> = self.block()?;
+            return Ok(Stmt::Block(Box::from(Stmt::new_block_stmt(
+                stmts,
+                self.span_from(start),
+            ))));
+        }
+        self.expression_statement()
     }
 
-    fn print_statement(&mut self) -> Stmt {
-        let expr: Expr = self.expression();
-        self.consume(TokenType::SemiColon, "Expect ';' after value".to_string());
-        return Stmt::Print(Box::from(Stmt::new_print_stmt(expr)));
+    fn for_statement(&mut self) -> Result<Stmt, ParseError> {
+        let start: Span = self.previous().get_span();
+        self.consume(TokenType::LeftParen, "Expect '(' after 'for'".to_string())?;
+
+        let initializer: Option<Stmt> = if self.match_token(&[&TokenType::SemiColon]) {
+            None
+        } else if self.match_token(&[&TokenType::Var]) {
+            Some(self.var_declaration()?)
+        } else {
+            Some(self.expression_statement()?)
+        };
+
+        let mut condition: Option<Expr> = None;
+        if !self.check(TokenType::SemiColon) {
+            condition = Some(self.expression()?);
+        }
+        self.consume(
+            TokenType::SemiColon,
+            "Expect ';' after loop condition".to_string(),
+        )?;
+
+        let mut increment: Option<Expr> = None;
+        if !self.check(TokenType::RightParen) {
+            increment = Some(self.expression()?);
+        }
+        self.consume(
+            TokenType::RightParen,
+            "Expect ')' after for clauses".to_string(),
+        )?;
+
+        // Desugar into a while loop: { init; while (cond) { body; incr; } }
+        // The desugared nodes all cover the whole for statement
+        let mut body: Stmt = self.statement()?;
+        let span: Span = self.span_from(start);
+        if let Some(increment) = increment {
+            let increment_span: Span = increment.get_span();
+            body = Stmt::Block(Box::from(Stmt::new_block_stmt(
+                vec![
+                    body,
+                    Stmt::Expression(Box::from(Stmt::new_exp_stmt(increment, increment_span))),
+                ],
+                span,
+            )));
+        }
+
+        // A missing condition loops forever
+        let condition: Expr = condition.unwrap_or(Expr::new_literal(Value::Bool(true), start));
+        body = Stmt::While(Box::from(Stmt::new_while_stmt(condition, body, span)));
+
+        if let Some(initializer) = initializer {
+            body = Stmt::Block(Box::from(Stmt::new_block_stmt(
+                vec![initializer, body],
+                span,
+            )));
+        }
+        Ok(body)
     }
 
-    fn expression_statement(&mut self) -> Stmt {
-        let expr: Expr = self.expression();
+    fn if_statement(&mut self) -> Result<Stmt, ParseError> {
+        let start: Span = self.previous().get_span();
+        self.consume(TokenType::LeftParen, "Expect '(' after 'if'".to_string())?;
+        let condition: Expr = self.expression()?;
+        self.consume(
+            TokenType::RightParen,
+            "Expect ')' after if condition".to_string(),
+        )?;
+
+        let then_branch: Stmt = self.statement()?;
+        let mut else_branch: Option<Stmt> = None;
+        // The else binds to the nearest if
+        if self.match_token(&[&TokenType::Else]) {
+            else_branch = Some(self.statement()?);
+        }
+        Ok(Stmt::If(Box::from(Stmt::new_if_stmt(
+            condition,
+            then_branch,
+            else_branch,
+            self.span_from(start),
+        ))))
+    }
+
+    fn while_statement(&mut self) -> Result<Stmt, ParseError> {
+        let start: Span = self.previous().get_span();
+        self.consume(TokenType::LeftParen, "Expect '(' after 'while'".to_string())?;
+        let condition: Expr = self.expression()?;
+        self.consume(
+            TokenType::RightParen,
+            "Expect ')' after condition".to_string(),
+        )?;
+        let body: Stmt = self.statement()?;
+        Ok(Stmt::While(Box::from(Stmt::new_while_stmt(
+            condition,
+            body,
+            self.span_from(start),
+        ))))
+    }
+
+    fn block(&mut self) -> Result<Vec<Stmt>, ParseError> {
+        let mut stmts: Vec<Stmt> = Vec::new();
+        // Keep parsing declarations till the block is closed
+        while !self.check(TokenType::RightBrace) && !self.is_at_end() {
+            if let Some(stmt) = self.declaration() {
+                stmts.push(stmt);
+            }
+        }
+        self.consume(TokenType::RightBrace, "Expect '}' after block".to_string())?;
+        Ok(stmts)
+    }
+
+    fn print_statement(&mut self) -> Result<Stmt, ParseError> {
+        let keyword: Token = self.previous();
+        let expr: Expr = self.expression()?;
+        self.consume(TokenType::SemiColon, "Expect ';' after value".to_string())?;
+        let span: Span = self.span_from(keyword.get_span());
+        Ok(Stmt::Print(Box::from(Stmt::new_print_stmt(
+            keyword, expr, span,
+        ))))
+    }
+
+    fn return_statement(&mut self) -> Result<Stmt, ParseError> {
+        let keyword: Token = self.previous();
+        let mut value: Option<Expr> = None;
+        if !self.check(TokenType::SemiColon) {
+            value = Some(self.expression()?);
+        }
+        self.consume(
+            TokenType::SemiColon,
+            "Expect ';' after return value".to_string(),
+        )?;
+        let span: Span = self.span_from(keyword.get_span());
+        Ok(Stmt::Return(Box::from(Stmt::new_return_stmt(
+            keyword, value, span,
+        ))))
+    }
+
+    fn expression_statement(&mut self) -> Result<Stmt, ParseError> {
+        let expr: Expr = self.expression()?;
         self.consume(
             TokenType::SemiColon,
             "Expect ';' after statement".to_string(),
-        );
-        return Stmt::Expression(Box::from(Stmt::new_exp_stmt(expr)));
+        )?;
+        let span: Span = self.span_from(expr.get_span());
+        Ok(Stmt::Expression(Box::from(Stmt::new_exp_stmt(expr, span))))
     }
 
-    fn expression(&mut self) -> Expr {
+    fn expression(&mut self) -> Result<Expr, ParseError> {
         self.assignment()
     }
 
-    fn assignment(&mut self) -> Expr {
+    fn assignment(&mut self) -> Result<Expr, ParseError> {
         // Get the lhs
-        let expr: Expr = self.equality();
+        let expr: Expr = self.or()?;
 
         // Now we check if the current token is an Equal, if it is, it's an assignment
         if self.match_token(&[&TokenType::Equal]) {
             let equals: Token = self.previous();
-            let val: Expr = self.assignment();
+            let val: Expr = self.assignment()?;
 
             // Now if the original expression is not a variable, invalid assignment
-            match expr {
+            match &expr {
                 Expr::Variable(expr) => {
-                    let name: Token = Expr::get_var_name(*expr);
-                    return Expr::new_assign_expr(name, val);
-                },
+                    let name: Token = Expr::get_var_name(expr).clone();
+                    return Ok(Expr::new_assign_expr(name, val));
+                }
+                Expr::Get(expr) => {
+                    // A property access on the lhs turns into a property set
+                    return Ok(Expr::new_set(
+                        Expr::get_get_object(expr).clone(),
+                        Expr::get_get_name(expr).clone(),
+                        val,
+                    ));
+                }
                 _ => {
-                    // Should not be any other type
-                    self.rlox.parse_error(equals, "Invalid assignment target".to_string());
-                    unreachable!()
+                    // Reported but not thrown, the parser is still in a sane state
+                    self.rlox
+                        .parse_error(equals, "Invalid assignment target".to_string());
                 }
             }
         }
         // Return expr if not equal
-        expr
+        Ok(expr)
+    }
+
+    fn or(&mut self) -> Result<Expr, ParseError> {
+        let mut expr: Expr = self.and()?;
+        while self.match_token(&[&TokenType::Or]) {
+            let operator: Token = self.previous();
+            let right: Expr = self.and()?;
+            expr = Expr::new_logical(expr, operator, right);
+        }
+        Ok(expr)
     }
 
-    fn equality(&mut self) -> Expr {
-        let mut expr: Expr = self.comparison();
+    fn and(&mut self) -> Result<Expr, ParseError> {
+        let mut expr: Expr = self.equality()?;
+        while self.match_token(&[&TokenType::And]) {
+            let operator: Token = self.previous();
+            let right: Expr = self.equality()?;
+            expr = Expr::new_logical(expr, operator, right);
+        }
+        Ok(expr)
+    }
+
+    fn equality(&mut self) -> Result<Expr, ParseError> {
+        let mut expr: Expr = self.comparison()?;
         while self.match_token(&[&TokenType::BangEqual, &TokenType::EqualEqual]) {
             // Take the previous token as we have already matched with the token
             let operator: Token = self.previous();
-            let right_expr: Expr = self.comparison();
+            let right_expr: Expr = self.comparison()?;
             expr = Expr::new_binary(expr, operator, right_expr);
         }
-        expr
+        Ok(expr)
     }
 
     fn match_token(&mut self, tokens: &[&TokenType]) -> bool {
@@ -137,8 +415,8 @@ impl Parser<'_> {
         self.previous()
     }
 
-    fn comparison(&mut self) -> Expr {
-        let mut expr: Expr = self.term();
+    fn comparison(&mut self) -> Result<Expr, ParseError> {
+        let mut expr: Expr = self.term()?;
 
         while self.match_token(&[
             &TokenType::Greater,
@@ -147,102 +425,169 @@ impl Parser<'_> {
             &TokenType::Less,
         ]) {
             let operator: Token = self.previous();
-            let right: Expr = self.term();
+            let right: Expr = self.term()?;
             expr = Expr::new_binary(expr, operator, right);
         }
-        expr
+        Ok(expr)
     }
 
-    fn term(&mut self) -> Expr {
-        let mut expr: Expr = self.factor();
+    fn term(&mut self) -> Result<Expr, ParseError> {
+        let mut expr: Expr = self.factor()?;
 
         while self.match_token(&[&TokenType::Minus, &TokenType::Plus]) {
             let operator: Token = self.previous();
-            let right: Expr = self.factor();
+            let right: Expr = self.factor()?;
             expr = Expr::new_binary(expr, operator, right);
         }
-        expr
+        Ok(expr)
     }
 
-    fn factor(&mut self) -> Expr {
-        let mut expr: Expr = self.unary();
+    fn factor(&mut self) -> Result<Expr, ParseError> {
+        let mut expr: Expr = self.unary()?;
 
         while self.match_token(&[&TokenType::Slash, &TokenType::Star]) {
             let operator: Token = self.previous();
-            let right: Expr = self.unary();
+            let right: Expr = self.unary()?;
             expr = Expr::new_binary(expr, operator, right);
         }
-        expr
+        Ok(expr)
     }
 
-    fn unary(&mut self) -> Expr {
+    fn unary(&mut self) -> Result<Expr, ParseError> {
         if self.match_token(&[&TokenType::Bang, &TokenType::Minus]) {
             let operator: Token = self.previous();
-            let right: Expr = self.unary();
-            return Expr::new_unary(operator, right);
+            let right: Expr = self.unary()?;
+            return Ok(Expr::new_unary(operator, right));
         }
-        self.primary().unwrap()
+        self.call()
     }
 
-    fn primary(&mut self) -> Option<Expr> {
+    fn call(&mut self) -> Result<Expr, ParseError> {
+        let mut expr: Expr = self.primary()?;
+        // Calls and property accesses can be chained, e.g. a.b(1)(2)
+        loop {
+            if self.match_token(&[&TokenType::LeftParen]) {
+                expr = self.finish_call(expr)?;
+            } else if self.match_token(&[&TokenType::Dot]) {
+                let name: Token = self.consume(
+                    TokenType::Identifier,
+                    "Expect property name after '.'".to_string(),
+                )?;
+                expr = Expr::new_get(expr, name);
+            } else {
+                break;
+            }
+        }
+        Ok(expr)
+    }
+
+    fn finish_call(&mut self, callee: Expr) -> Result<Expr, ParseError> {
+        let mut args: Vec<Expr> = Vec::new();
+        if !self.check(TokenType::RightParen) {
+            loop {
+                if args.len() >= 255 {
+                    self.rlox.parse_error(
+                        self.peek(),
+                        "Can't have more than 255 arguments".to_string(),
+                    );
+                }
+                args.push(self.expression()?);
+                if !self.match_token(&[&TokenType::Comma]) {
+                    break;
+                }
+            }
+        }
+        let paren: Token = self.consume(
+            TokenType::RightParen,
+            "Expect ')' after arguments".to_string(),
+        )?;
+        Ok(Expr::new_call(callee, paren, args))
+    }
+
+    fn primary(&mut self) -> Result<Expr, ParseError> {
         if self.match_token(&[&TokenType::False]) {
-            return Some(Expr::new_literal(Object::Bool, self.previous()));
+            return Ok(Expr::new_literal(
+                Value::Bool(false),
+                self.previous().get_span(),
+            ));
         } else if self.match_token(&[&TokenType::True]) {
-            return Some(Expr::new_literal(Object::Bool, self.previous()));
+            return Ok(Expr::new_literal(
+                Value::Bool(true),
+                self.previous().get_span(),
+            ));
         } else if self.match_token(&[&TokenType::Nil]) {
-            return Some(Expr::new_literal(Object::Nil, self.previous()));
-        } else if self.match_token(&[&TokenType::Number]) {
-            return Some(Expr::new_literal(Object::Number, self.previous()));
+            return Ok(Expr::new_literal(Value::Nil, self.previous().get_span()));
+        } else if self.match_token(&[&TokenType::Number, &TokenType::String]) {
+            // The scanner has already worked out the value
+            let val: Value = match self.previous().get_literal() {
+                Object::Number(num) => Value::Number(num),
+                Object::String(str) => Value::String(Symbol::from(str)),
+                _ => unreachable!(),
+            };
+            return Ok(Expr::new_literal(val, self.previous().get_span()));
+        } else if self.match_token(&[&TokenType::Super]) {
+            let keyword: Token = self.previous();
+            self.consume(TokenType::Dot, "Expect '.' after 'super'".to_string())?;
+            let method: Token = self.consume(
+                TokenType::Identifier,
+                "Expect superclass method name".to_string(),
+            )?;
+            return Ok(Expr::new_super(keyword, method));
+        } else if self.match_token(&[&TokenType::This]) {
+            return Ok(Expr::new_this(self.previous()));
         } else if self.match_token(&[&TokenType::Identifier]) {
-            return Some(Expr::new_variable(self.previous()));
-        } else if self.match_token(&[&TokenType::String]) {
-            return Some(Expr::new_literal(Object::String, self.previous()));
+            return Ok(Expr::new_variable(self.previous()));
         } else if self.match_token(&[&TokenType::LeftParen]) {
+            let start: Span = self.previous().get_span();
             // Consume the enclosing expression
-            let expr: Expr = self.expression();
-            if self.consume(
+            let expr: Expr = self.expression()?;
+            self.consume(
                 TokenType::RightParen,
                 "Expected ')' after expression".to_string(),
-            ) {
-                return Some(Expr::new_grouping(expr));
-            }
-            return None;
+            )?;
+            return Ok(Expr::new_grouping(expr, self.span_from(start)));
         }
-        self.error(self.peek(), "Expect expression.".to_string());
-        None
+        Err(ParseError::new(
+            self.peek(),
+            "Expect expression".to_string(),
+        ))
+    }
+
+    // Span from `start` up to the end of the last consumed token
+    fn span_from(&self, start: Span) -> Span {
+        Span::merge(start, self.previous().get_span())
     }
 
     fn synchronise(&mut self) {
         self.advance();
-        // Discard everything till the current line is done
+        // Discard tokens till we are at what looks like the start of the next statement
         while !self.is_at_end() {
             if self.previous().get_type() == TokenType::SemiColon {
                 return;
             }
 
             match self.peek().get_type() {
-                TokenType::Return => return,
+                TokenType::Class
+                | TokenType::Fun
+                | TokenType::Var
+                | TokenType::For
+                | TokenType::If
+                | TokenType::While
+                | TokenType::Print
+                | TokenType::Return => return,
                 _ => {}
             }
-            // Go to the next character
+            // Go to the next token
             self.advance();
         }
     }
 
-    fn consume(&mut self, token_type: TokenType, message: String) -> bool {
+    fn consume(&mut self, token_type: TokenType, message: String) -> Result<Token, ParseError> {
         if self.check(token_type) {
             // Go to the next token
-            self.advance();
-            return true;
+            return Ok(self.advance());
         }
-        // Throw error
-        self.rlox.parse_error(self.peek(), message);
-        return false;
-    }
-
-    fn error(&mut self, token: Token, message: String) -> Option<bool> {
-        self.rlox.parse_error(token, message);
-        None
+        Err(ParseError::new(self.peek(), message))
     }
 
     fn is_at_end(&self) -> bool {
@@ -0,0 +1,384 @@
+use crate::expr::{
+    AssignmentExpression, BinaryExpression, CallExpression, Expr, GetExpression,
+    GroupingExpression, LogicalExpression, SetExpression, SuperExpression, ThisExpression,
+    UnaryExpression, VariableExpression,
+};
+use crate::intern::Symbol;
+use crate::stmt::{
+    BlockStatement, ClassStatement, ExpressionStatement, FunctionStatement, IfStatement,
+    PrintStatement, ReturnStatement, Stmt, VarStmt, WhileStatement,
+};
+use crate::token::Token;
+use crate::Rlox;
+use std::collections::{HashMap, HashSet};
+
+#[derive(Clone, Copy, PartialEq)]
+enum FunctionType {
+    None,
+    Function,
+    Initializer,
+    Method,
+}
+
+#[derive(Clone, Copy, PartialEq)]
+enum ClassType {
+    None,
+    Class,
+    Subclass,
+}
+
+pub struct Resolver<'a> {
+    // Each scope maps a name to whether its initializer has finished resolving
+    scopes: Vec<HashMap<Symbol, bool>>,
+    current_function: FunctionType,
+    current_class: ClassType,
+    // Names declared at the top level, only tracked in strict mode
+    globals: HashSet<Symbol>,
+    this_string: Symbol,
+    super_string: Symbol,
+    init_string: Symbol,
+    rlox: &'a mut Rlox,
+}
+
+impl Resolver<'_> {
+    pub fn new(rlox: &mut Rlox) -> Resolver<'_> {
+        Resolver {
+            scopes: Vec::new(),
+            current_function: FunctionType::None,
+            current_class: ClassType::None,
+            globals: HashSet::new(),
+            this_string: Symbol::intern("this"),
+            super_string: Symbol::intern("super"),
+            init_string: Symbol::intern("init"),
+            rlox,
+        }
+    }
+
+    // Globals that already exist before this program, e.g. from an earlier REPL line
+    pub fn declare_globals(&mut self, names: Vec<Symbol>) {
+        self.globals.extend(names);
+    }
+
+    pub fn resolve(&mut self, stmts: &[Stmt]) {
+        if self.rlox.strict {
+            // Collect globals up front so functions can assign to ones declared after them
+            for stmt in stmts {
+                let name: Option<Symbol> = match stmt {
+                    Stmt::Var(stmt) => Some(Stmt::get_var_key(stmt)),
+                    Stmt::Function(stmt) => Some(Stmt::get_function_name(stmt).get_name()),
+                    Stmt::Class(stmt) => Some(Stmt::get_class_name(stmt).get_name()),
+                    _ => None,
+                };
+                if let Some(name) = name {
+                    self.globals.insert(name);
+                }
+            }
+        }
+        self.resolve_stmts(stmts);
+    }
+
+    fn resolve_stmts(&mut self, stmts: &[Stmt]) {
+        for stmt in stmts {
+            self.resolve_stmt(stmt);
+        }
+    }
+
+    fn resolve_stmt(&mut self, stmt: &Stmt) {
+        match stmt {
+            Stmt::Block(stmt) => self.visit_block_stmt(stmt),
+            Stmt::Class(stmt) => self.visit_class_stmt(stmt),
+            Stmt::Expression(stmt) => self.visit_expr_stmt(stmt),
+            Stmt::Function(stmt) => self.visit_function_stmt(stmt),
+            Stmt::If(stmt) => self.visit_if_stmt(stmt),
+            Stmt::Print(stmt) => self.visit_print_stmt(stmt),
+            Stmt::Return(stmt) => self.visit_return_stmt(stmt),
+            Stmt::Var(stmt) => self.visit_var_stmt(stmt),
+            Stmt::While(stmt) => self.visit_while_stmt(stmt),
+        }
+    }
+
+    fn resolve_expr(&mut self, expr: &Expr) {
+        match expr {
+            Expr::Assign(expr) => self.visit_assign_expr(expr),
+            Expr::Binary(expr) => self.visit_binary_expr(expr),
+            Expr::Call(expr) => self.visit_call_expr(expr),
+            Expr::Get(expr) => self.visit_get_expr(expr),
+            Expr::Grouping(expr) => self.visit_group_expr(expr),
+            Expr::Literal(_) => {}
+            Expr::Logical(expr) => self.visit_logical_expr(expr),
+            Expr::Set(expr) => self.visit_set_expr(expr),
+            Expr::Super(expr) => self.visit_super_expr(expr),
+            Expr::This(expr) => self.visit_this_expr(expr),
+            Expr::Unary(expr) => self.visit_unary_expr(expr),
+            Expr::Variable(expr) => self.visit_variable_expr(expr),
+        }
+    }
+
+    fn begin_scope(&mut self) {
+        self.scopes.push(HashMap::new());
+    }
+
+    fn end_scope(&mut self) {
+        self.scopes.pop();
+    }
+
+    fn declare(&mut self, name: &Token) {
+        let already_declared: bool = match self.scopes.last_mut() {
+            Some(scope) => scope.insert(name.get_name(), false).is_some(),
+            // Globals are not tracked
+            None => return,
+        };
+        if already_declared {
+            self.rlox.resolve_error(
+                name.clone(),
+                "Already a variable with this name in this scope".to_string(),
+            );
+        }
+    }
+
+    fn define(&mut self, name: &Token) {
+        if let Some(scope) = self.scopes.last_mut() {
+            scope.insert(name.get_name(), true);
+        }
+    }
+
+    // Number of scopes between the innermost one and the one binding `name`, None for globals
+    fn resolve_local(&self, name: &Token) -> Option<usize> {
+        let name: Symbol = name.get_name();
+        self.scopes
+            .iter()
+            .rev()
+            .position(|scope| scope.contains_key(&name))
+    }
+
+    fn resolve_function(&mut self, stmt: &FunctionStatement, function_type: FunctionType) {
+        let enclosing_function = self.current_function;
+        self.current_function = function_type;
+
+        self.begin_scope();
+        for param in Stmt::get_function_params(stmt) {
+            self.declare(param);
+            self.define(param);
+        }
+        self.resolve_stmts(Stmt::get_function_body(stmt));
+        self.end_scope();
+
+        self.current_function = enclosing_function;
+    }
+
+    fn visit_block_stmt(&mut self, stmt: &BlockStatement) {
+        self.begin_scope();
+        self.resolve_stmts(Stmt::get_block_stmts(stmt));
+        self.end_scope();
+    }
+
+    fn visit_class_stmt(&mut self, stmt: &ClassStatement) {
+        let enclosing_class = self.current_class;
+        self.current_class = ClassType::Class;
+
+        let name: &Token = Stmt::get_class_name(stmt);
+        self.declare(name);
+        self.define(name);
+
+        let superclass: Option<&Expr> = Stmt::get_class_superclass(stmt);
+        if let Some(superclass) = superclass {
+            if let Expr::Variable(variable) = superclass {
+                let superclass_name: &Token = Expr::get_var_name(variable);
+                if superclass_name.get_name() == name.get_name() {
+                    self.rlox.resolve_error(
+                        superclass_name.clone(),
+                        "A class can't inherit from itself".to_string(),
+                    );
+                }
+            }
+            self.current_class = ClassType::Subclass;
+            self.resolve_expr(superclass);
+
+            // Subclass methods close over a scope that binds `super`
+            self.begin_scope();
+            if let Some(scope) = self.scopes.last_mut() {
+                scope.insert(self.super_string.clone(), true);
+            }
+        }
+
+        // Methods close over a scope that binds `this`
+        self.begin_scope();
+        if let Some(scope) = self.scopes.last_mut() {
+            scope.insert(self.this_string.clone(), true);
+        }
+        for method in Stmt::get_class_methods(stmt) {
+            let mut function_type = FunctionType::Method;
+            if Stmt::get_function_name(method).get_name() == self.init_string {
+                function_type = FunctionType::Initializer;
+            }
+            self.resolve_function(method, function_type);
+        }
+        self.end_scope();
+
+        if superclass.is_some() {
+            self.end_scope();
+        }
+
+        self.current_class = enclosing_class;
+    }
+
+    fn visit_expr_stmt(&mut self, stmt: &ExpressionStatement) {
+        self.resolve_expr(Stmt::get_expr_stmt_expr(stmt));
+    }
+
+    fn visit_function_stmt(&mut self, stmt: &FunctionStatement) {
+        // Define eagerly so the function can refer to itself recursively
+        let name: &Token = Stmt::get_function_name(stmt);
+        self.declare(name);
+        self.define(name);
+        self.resolve_function(stmt, FunctionType::Function);
+    }
+
+    fn visit_if_stmt(&mut self, stmt: &IfStatement) {
+        self.resolve_expr(Stmt::get_if_condition(stmt));
+        self.resolve_stmt(Stmt::get_if_then(stmt));
+        if let Some(else_branch) = Stmt::get_if_else(stmt) {
+            self.resolve_stmt(else_branch);
+        }
+    }
+
+    fn visit_print_stmt(&mut self, stmt: &PrintStatement) {
+        self.resolve_expr(Stmt::get_print_stmt_expr(stmt));
+    }
+
+    fn visit_return_stmt(&mut self, stmt: &ReturnStatement) {
+        if self.current_function == FunctionType::None {
+            self.rlox.resolve_error(
+                Stmt::get_return_keyword(stmt).clone(),
+                "Can't return from top-level code".to_string(),
+            );
+        }
+        if let Some(value) = Stmt::get_return_value(stmt) {
+            if self.current_function == FunctionType::Initializer {
+                self.rlox.resolve_error(
+                    Stmt::get_return_keyword(stmt).clone(),
+                    "Can't return a value from an initializer".to_string(),
+                );
+            }
+            self.resolve_expr(value);
+        }
+    }
+
+    fn visit_var_stmt(&mut self, stmt: &VarStmt) {
+        // Declare and define are split so the initializer can't see the variable
+        let name: &Token = Stmt::get_var_name(stmt);
+        self.declare(name);
+        if let Some(initializer) = Stmt::get_var_initializer(stmt) {
+            self.resolve_expr(initializer);
+        }
+        self.define(name);
+    }
+
+    fn visit_while_stmt(&mut self, stmt: &WhileStatement) {
+        self.resolve_expr(Stmt::get_while_condition(stmt));
+        self.resolve_stmt(Stmt::get_while_body(stmt));
+    }
+
+    fn visit_assign_expr(&mut self, expr: &AssignmentExpression) {
+        self.resolve_expr(Expr::get_assign_val(expr));
+        let name: &Token = Expr::get_assign_name(expr);
+        let distance: Option<usize> = self.resolve_local(name);
+        if let Some(distance) = distance {
+            Expr::set_assign_distance(expr, distance);
+        }
+
+        // Strict mode refuses to assign to a name that is never declared
+        if self.rlox.strict && distance.is_none() && !self.globals.contains(&name.get_name()) {
+            let message = format!("Undefined variable '{}'", name.get_lexeme());
+            self.rlox.resolve_error(name.clone(), message);
+        }
+    }
+
+    fn visit_binary_expr(&mut self, expr: &BinaryExpression) {
+        self.resolve_expr(Expr::get_binary_left(expr));
+        self.resolve_expr(Expr::get_binary_right(expr));
+    }
+
+    fn visit_call_expr(&mut self, expr: &CallExpression) {
+        self.resolve_expr(Expr::get_call_callee(expr));
+        for arg in Expr::get_call_args(expr) {
+            self.resolve_expr(arg);
+        }
+    }
+
+    fn visit_get_expr(&mut self, expr: &GetExpression) {
+        // Properties are looked up dynamically, only the object is resolved
+        self.resolve_expr(Expr::get_get_object(expr));
+    }
+
+    fn visit_group_expr(&mut self, expr: &GroupingExpression) {
+        self.resolve_expr(Expr::get_grouping_expr(expr));
+    }
+
+    fn visit_logical_expr(&mut self, expr: &LogicalExpression) {
+        self.resolve_expr(Expr::get_logical_left(expr));
+        self.resolve_expr(Expr::get_logical_right(expr));
+    }
+
+    fn visit_set_expr(&mut self, expr: &SetExpression) {
+        self.resolve_expr(Expr::get_set_val(expr));
+        self.resolve_expr(Expr::get_set_object(expr));
+    }
+
+    fn visit_super_expr(&mut self, expr: &SuperExpression) {
+        let keyword: &Token = Expr::get_super_keyword(expr);
+        match self.current_class {
+            ClassType::None => {
+                self.rlox.resolve_error(
+                    keyword.clone(),
+                    "Can't use 'super' outside of a class".to_string(),
+                );
+                return;
+            }
+            ClassType::Class => {
+                self.rlox.resolve_error(
+                    keyword.clone(),
+                    "Can't use 'super' in a class with no superclass".to_string(),
+                );
+                return;
+            }
+            ClassType::Subclass => {}
+        }
+        if let Some(distance) = self.resolve_local(keyword) {
+            Expr::set_super_distance(expr, distance);
+        }
+    }
+
+    fn visit_this_expr(&mut self, expr: &ThisExpression) {
+        let keyword: &Token = Expr::get_this_keyword(expr);
+        if self.current_class == ClassType::None {
+            self.rlox.resolve_error(
+                keyword.clone(),
+                "Can't use 'this' outside of a class".to_string(),
+            );
+            return;
+        }
+        if let Some(distance) = self.resolve_local(keyword) {
+            Expr::set_this_distance(expr, distance);
+        }
+    }
+
+    fn visit_unary_expr(&mut self, expr: &UnaryExpression) {
+        self.resolve_expr(Expr::get_unary_expr(expr));
+    }
+
+    fn visit_variable_expr(&mut self, expr: &VariableExpression) {
+        let name: &Token = Expr::get_var_name(expr);
+        if let Some(scope) = self.scopes.last() {
+            if scope.get(&name.get_name()) == Some(&false) {
+                self.rlox.resolve_error(
+                    name.clone(),
+                    "Can't read local variable in its own initializer".to_string(),
+                );
+            }
+        }
+        if let Some(distance) = self.resolve_local(name) {
+            Expr::set_var_distance(expr, distance);
+        }
+    }
+}
@@ -1,8 +1,13 @@
 use crate::token::Object;
-use crate::token::Token;
+use crate::token::{Span, Token};
 use crate::token_type::TokenType;
 use crate::Rlox;
 use ::phf::{phf_map, Map};
+use std::sync::atomic::{AtomicU32, Ordering};
+
+// Ids handed to scanned sources, functions outlive the `eval` that defined them so their spans
+// have to say which text they point into
+static NEXT_SOURCE_ID: AtomicU32 = AtomicU32::new(1);
 
 static KEYWORDS: Map<&'static str, TokenType> = phf_map! {
     "and" => TokenType::And,
@@ -27,14 +32,21 @@ static KEYWORDS: Map<&'static str, TokenType> = phf_map! {
 pub struct Scanner<'a> {
     source: String,
     tokens: Vec<Token>,
+    // Byte offsets into the source
     current: usize,
     start: usize,
     line: i32,
+    // Column of `current`, counting characters rather than bytes from 1
+    column: usize,
+    // Line and column the current token began at
+    start_line: i32,
+    start_column: usize,
+    source_id: u32,
     rlox: &'a mut Rlox,
 }
 
 impl Scanner<'_> {
-    pub fn new(source: String, rlox: &mut Rlox) -> Scanner {
+    pub fn new(source: String, rlox: &mut Rlox) -> Scanner<'_> {
         let empty = Vec::new();
         Scanner {
             source,
@@ -42,72 +54,108 @@ impl Scanner<'_> {
             current: 0,
             start: 0,
             line: 1,
+            column: 1,
+            start_line: 1,
+            start_column: 1,
+            source_id: NEXT_SOURCE_ID.fetch_add(1, Ordering::Relaxed),
             rlox,
         }
     }
 
+    pub fn get_source_id(&self) -> u32 {
+        self.source_id
+    }
+
     fn is_at_end(&self) -> bool {
-        return self.current >= self.source.len();
+        self.current >= self.source.len()
     }
 
     fn advance(&mut self) -> char {
-        let char = self.source.chars().nth(self.current).unwrap();
-        self.current += 1;
-        return char;
+        let char = self.source[self.current..].chars().next().unwrap();
+        self.current += char.len_utf8();
+        self.column += 1;
+        char
+    }
+
+    fn new_line(&mut self) {
+        self.line += 1;
+        self.column = 1;
+    }
+
+    fn span(&self) -> Span {
+        Span::new(
+            self.start,
+            self.current - self.start,
+            self.start_line,
+            self.start_column,
+            self.source_id,
+        )
     }
 
     fn add_token(&mut self, token_type: TokenType) {
-        let text: String = self.source[self.start..self.current].to_string();
-        self.tokens
-            .push(Token::new(token_type, text, self.line, Object::Nil));
+        self.add_token_value(token_type, Object::Nil);
     }
 
     fn add_token_value(&mut self, token_type: TokenType, literal: Object) {
-        let mut text: String = self.source[self.start..self.current].to_string();
-        // Strip the " from the string
-        text = text.as_str().replace("\"", "").to_string();
+        let text: String = self.source[self.start..self.current].to_string();
+        let span: Span = self.span();
         self.tokens
-            .push(Token::new(token_type, text, self.line, literal));
+            .push(Token::new(token_type, text, literal, span));
     }
 
     fn match_next(&mut self, expected: char) -> bool {
-        if self.is_at_end() {
+        if self.peek() != expected {
             return false;
         }
-        if self.source.chars().nth(self.current).unwrap() != expected {
-            return false;
-        }
-        self.current += 1;
-        return true;
+        self.advance();
+        true
     }
 
     fn peek(&self) -> char {
-        if self.is_at_end() {
-            return '\0';
-        }
-        return self.source.chars().nth(self.current).unwrap();
+        self.source[self.current..].chars().next().unwrap_or('\0')
     }
 
     fn peek_next(&self) -> char {
-        if self.current + 1 >= self.source.len() {
-            return '\0';
-        }
-        return self.source.chars().nth(self.current + 1).unwrap();
+        self.source[self.current..].chars().nth(1).unwrap_or('\0')
     }
 
     fn string(&mut self) {
+        let mut value: String = String::new();
         // Iterate till you go to the end of the string and it has not ended
         while self.peek() != '"' && !self.is_at_end() {
-            // As multi-line comments are allowed, increment the line
-            if self.peek() == '\n' {
-                self.line += 1;
+            let c: char = self.advance();
+            match c {
+                // As multi-line strings are allowed, increment the line
+                '\n' => {
+                    self.new_line();
+                    value.push(c);
+                }
+                '\\' => {
+                    if self.is_at_end() {
+                        break;
+                    }
+                    // Unescape once here so the interpreter never has to
+                    match self.advance() {
+                        'n' => value.push('\n'),
+                        't' => value.push('\t'),
+                        'r' => value.push('\r'),
+                        '"' => value.push('"'),
+                        '\\' => value.push('\\'),
+                        escaped => {
+                            if escaped == '\n' {
+                                self.new_line();
+                            }
+                            self.rlox.error(self.span(), "Invalid escape sequence.");
+                        }
+                    }
+                }
+                _ => value.push(c),
             }
-            self.advance();
         }
 
         // If the string was not terminated
         if self.is_at_end() {
-            self.rlox.error(self.line, "Unterminated String!");
+            self.rlox.error(self.span(), "Unterminated String!");
             return;
         }
 
@@ -115,19 +163,19 @@ impl Scanner<'_> {
         self.advance(); // Consume the "
 
         // Get the string value
-        self.add_token_value(TokenType::String, Object::String);
+        self.add_token_value(TokenType::String, Object::String(value));
     }
 
     fn is_digit(&self, c: char) -> bool {
-        return c >= '0' && c <= '9';
+        c.is_ascii_digit()
     }
 
     fn is_alpha(&self, c: char) -> bool {
-        return (c >= 'a' && c <= 'z') || (c >= 'A' && c <= 'Z') || c == '_';
+        c.is_ascii_lowercase() || c.is_ascii_uppercase() || c == '_'
     }
 
     fn is_alpha_numeric(&self, c: char) -> bool {
-        return self.is_digit(c) || self.is_alpha(c);
+        self.is_digit(c) || self.is_alpha(c)
     }
 
     fn number(&mut self) {
@@ -147,7 +195,8 @@ impl Scanner<'_> {
         }
 
         // Now you get the number
-        self.add_token_value(TokenType::Number, Object::Number);
+        let value: f64 = self.source[self.start..self.current].parse().unwrap();
+        self.add_token_value(TokenType::Number, Object::Number(value));
     }
 
     fn identifier(&mut self) {
@@ -159,9 +208,8 @@ impl Scanner<'_> {
 
         let mut token_type: TokenType = TokenType::Identifier;
 
-        match KEYWORDS.get(text).cloned() {
-            Some(tok_type) => token_type = tok_type,
-            None => {}
+        if let Some(tok_type) = KEYWORDS.get(text).cloned() {
+            token_type = tok_type
         }
 
         self.add_token(token_type);
@@ -220,7 +268,7 @@ impl Scanner<'_> {
             ' ' => {}
             '\r' => {}
             '\t' => {}
-            '\n' => self.line += 1,
+            '\n' => self.new_line(),
             '"' => self.string(),
             _ => {
                 if self.is_digit(c) {
@@ -228,7 +276,7 @@ impl Scanner<'_> {
                 } else if self.is_alpha(c) {
                     self.identifier();
                 } else {
-                    self.rlox.error(self.line, "Unexpected character.");
+                    self.rlox.error(self.span(), "Unexpected character.");
                 }
             }
         }
@@ -237,15 +285,15 @@ impl Scanner<'_> {
     pub fn scan_tokens(&mut self) -> Vec<Token> {
         while !self.is_at_end() {
             self.start = self.current;
+            self.start_line = self.line;
+            self.start_column = self.column;
             self.scan_token();
         }
 
-        self.tokens.push(Token::new(
-            TokenType::EOF,
-            "".to_string(),
-            self.line,
-            Object::Nil,
-        ));
-        return self.tokens.clone();
+        self.start = self.current;
+        self.start_line = self.line;
+        self.start_column = self.column;
+        self.add_token(TokenType::EOF);
+        self.tokens.clone()
     }
 }
@@ -1,62 +1,264 @@
 use crate::expr::Expr;
-use crate::token::Token;
+use crate::intern::Symbol;
+use crate::token::{Span, Token};
+use std::rc::Rc;
 
 #[derive(Clone)]
 pub enum Stmt {
+    Block(Box<BlockStatement>),
+    Class(Box<ClassStatement>),
     Expression(Box<ExpressionStatement>),
+    // Shared with the functions the interpreter creates from it
+    Function(Rc<FunctionStatement>),
+    If(Box<IfStatement>),
     Print(Box<PrintStatement>),
+    Return(Box<ReturnStatement>),
     Var(Box<VarStmt>),
+    While(Box<WhileStatement>),
+}
+
+#[derive(Clone)]
+pub struct BlockStatement {
+    statements: Vec<Stmt>,
+    span: Span,
+}
+
+#[derive(Clone)]
+pub struct ClassStatement {
+    name: Token,
+    superclass: Option<Expr>,
+    methods: Vec<Rc<FunctionStatement>>,
+    span: Span,
 }
 
 #[derive(Clone)]
 pub struct ExpressionStatement {
     expression: Box<Expr>,
+    span: Span,
+}
+
+#[derive(Clone)]
+pub struct FunctionStatement {
+    name: Token,
+    params: Vec<Token>,
+    body: Vec<Stmt>,
+    span: Span,
+}
+
+#[derive(Clone)]
+pub struct IfStatement {
+    condition: Box<Expr>,
+    then_branch: Box<Stmt>,
+    else_branch: Option<Box<Stmt>>,
+    span: Span,
 }
 
 #[derive(Clone)]
 pub struct VarStmt {
     name: Token,
     initializer: Option<Expr>,
+    span: Span,
 }
 
 #[derive(Clone)]
 pub struct PrintStatement {
+    keyword: Token,
     expression: Box<Expr>,
+    span: Span,
+}
+
+#[derive(Clone)]
+pub struct ReturnStatement {
+    keyword: Token,
+    value: Option<Expr>,
+    span: Span,
+}
+
+#[derive(Clone)]
+pub struct WhileStatement {
+    condition: Box<Expr>,
+    body: Box<Stmt>,
+    span: Span,
 }
 
 impl Stmt {
-    pub fn get_expr_stmt_expr(stmt: ExpressionStatement) -> Expr {
-        (*stmt.expression).clone()
+    // Source range the statement was parsed from, for tooling that maps nodes back to source
+    pub fn get_span(&self) -> Span {
+        match self {
+            Stmt::Block(stmt) => stmt.span,
+            Stmt::Class(stmt) => stmt.span,
+            Stmt::Expression(stmt) => stmt.span,
+            Stmt::Function(stmt) => stmt.span,
+            Stmt::If(stmt) => stmt.span,
+            Stmt::Print(stmt) => stmt.span,
+            Stmt::Return(stmt) => stmt.span,
+            Stmt::Var(stmt) => stmt.span,
+            Stmt::While(stmt) => stmt.span,
+        }
+    }
+
+    pub fn get_expr_stmt_expr(stmt: &ExpressionStatement) -> &Expr {
+        &stmt.expression
+    }
+
+    pub fn get_print_stmt_expr(stmt: &PrintStatement) -> &Expr {
+        &stmt.expression
+    }
+
+    pub fn get_print_keyword(stmt: &PrintStatement) -> &Token {
+        &stmt.keyword
+    }
+
+    pub fn new_block_stmt(statements: Vec<Stmt>, span: Span) -> BlockStatement {
+        BlockStatement { statements, span }
+    }
+
+    pub fn get_block_stmts(stmt: &BlockStatement) -> &[Stmt] {
+        &stmt.statements
+    }
+
+    pub fn new_class_stmt(
+        name: Token,
+        superclass: Option<Expr>,
+        methods: Vec<Rc<FunctionStatement>>,
+        span: Span,
+    ) -> ClassStatement {
+        ClassStatement {
+            name,
+            superclass,
+            methods,
+            span,
+        }
+    }
+
+    pub fn get_class_superclass(stmt: &ClassStatement) -> Option<&Expr> {
+        stmt.superclass.as_ref()
     }
 
-    pub fn get_print_stmt_expr(stmt: PrintStatement) -> Expr {
-        (*stmt.expression).clone()
+    pub fn get_class_name(stmt: &ClassStatement) -> &Token {
+        &stmt.name
     }
 
-    pub fn new_exp_stmt(expr: Expr) -> ExpressionStatement {
+    pub fn get_class_methods(stmt: &ClassStatement) -> &[Rc<FunctionStatement>] {
+        &stmt.methods
+    }
+
+    pub fn new_exp_stmt(expr: Expr, span: Span) -> ExpressionStatement {
         ExpressionStatement {
             expression: Box::from(expr),
+            span,
         }
     }
 
-    pub fn new_print_stmt(expr: Expr) -> PrintStatement {
+    pub fn new_print_stmt(keyword: Token, expr: Expr, span: Span) -> PrintStatement {
         PrintStatement {
+            keyword,
             expression: Box::from(expr),
+            span,
         }
     }
 
-    pub fn new_var_stmt(tok: Token, expr: Option<Expr>) -> VarStmt {
+    pub fn new_var_stmt(tok: Token, expr: Option<Expr>, span: Span) -> VarStmt {
         VarStmt {
             name: tok,
             initializer: expr,
+            span,
+        }
+    }
+
+    pub fn get_var_initializer(stmt: &VarStmt) -> Option<&Expr> {
+        stmt.initializer.as_ref()
+    }
+
+    pub fn get_var_name(stmt: &VarStmt) -> &Token {
+        &stmt.name
+    }
+
+    pub fn get_var_key(stmt: &VarStmt) -> Symbol {
+        stmt.name.get_name()
+    }
+
+    pub fn new_if_stmt(
+        condition: Expr,
+        then_branch: Stmt,
+        else_branch: Option<Stmt>,
+        span: Span,
+    ) -> IfStatement {
+        IfStatement {
+            condition: Box::from(condition),
+            then_branch: Box::from(then_branch),
+            else_branch: else_branch.map(Box::from),
+            span,
+        }
+    }
+
+    pub fn get_if_condition(stmt: &IfStatement) -> &Expr {
+        &stmt.condition
+    }
+
+    pub fn get_if_then(stmt: &IfStatement) -> &Stmt {
+        &stmt.then_branch
+    }
+
+    pub fn get_if_else(stmt: &IfStatement) -> Option<&Stmt> {
+        stmt.else_branch.as_deref()
+    }
+
+    pub fn new_while_stmt(condition: Expr, body: Stmt, span: Span) -> WhileStatement {
+        WhileStatement {
+            condition: Box::from(condition),
+            body: Box::from(body),
+            span,
+        }
+    }
+
+    pub fn get_while_condition(stmt: &WhileStatement) -> &Expr {
+        &stmt.condition
+    }
+
+    pub fn get_while_body(stmt: &WhileStatement) -> &Stmt {
+        &stmt.body
+    }
+
+    pub fn new_function_stmt(
+        name: Token,
+        params: Vec<Token>,
+        body: Vec<Stmt>,
+        span: Span,
+    ) -> FunctionStatement {
+        FunctionStatement {
+            name,
+            params,
+            body,
+            span,
+        }
+    }
+
+    pub fn get_function_name(stmt: &FunctionStatement) -> &Token {
+        &stmt.name
+    }
+
+    pub fn get_function_params(stmt: &FunctionStatement) -> &[Token] {
+        &stmt.params
+    }
+
+    pub fn get_function_body(stmt: &FunctionStatement) -> &[Stmt] {
+        &stmt.body
+    }
+
+    pub fn new_return_stmt(keyword: Token, value: Option<Expr>, span: Span) -> ReturnStatement {
+        ReturnStatement {
+            keyword,
+            value,
+            span,
         }
     }
 
-    pub fn get_var_initializer(stmt: VarStmt) -> Option<Expr> {
-        stmt.initializer
+    pub fn get_return_keyword(stmt: &ReturnStatement) -> &Token {
+        &stmt.keyword
     }
 
-    pub fn get_var_key(stmt: VarStmt) -> String {
-        stmt.name.get_lexeme()
+    pub fn get_return_value(stmt: &ReturnStatement) -> Option<&Expr> {
+        stmt.value.as_ref()
     }
 }
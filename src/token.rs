@@ -1,49 +1,118 @@
+use crate::intern::Symbol;
 use crate::token_type::TokenType;
+use std::fmt;
 
-#[derive(Clone, Debug, Copy)]
+// Literal value carried by a token, worked out once by the scanner
+#[derive(Clone, Debug)]
 pub enum Object {
     // None,
-    String,
-    Number,
-    Bool,
+    String(String),
+    Number(f64),
     Nil,
 }
 
+// Where a piece of source text lives, offsets and lengths are in bytes
+#[derive(Clone, Copy, Debug, Default, PartialEq)]
+pub struct Span {
+    offset: usize,
+    len: usize,
+    line: i32,
+    column: usize,
+    // Which scanned source the text is in, 0 for spans made up by the interpreter
+    source: u32,
+}
+
+impl Span {
+    pub fn new(offset: usize, len: usize, line: i32, column: usize, source: u32) -> Span {
+        Span {
+            offset,
+            len,
+            line,
+            column,
+            source,
+        }
+    }
+
+    // Smallest span covering both, the position is taken from `start`
+    pub fn merge(start: Span, end: Span) -> Span {
+        let finish = usize::max(start.offset + start.len, end.offset + end.len);
+        Span {
+            len: finish - start.offset,
+            ..start
+        }
+    }
+
+    pub fn get_offset(&self) -> usize {
+        self.offset
+    }
+
+    pub fn get_len(&self) -> usize {
+        self.len
+    }
+
+    pub fn get_line(&self) -> i32 {
+        self.line
+    }
+
+    pub fn get_column(&self) -> usize {
+        self.column
+    }
+
+    pub fn get_source(&self) -> u32 {
+        self.source
+    }
+}
+
 #[derive(Debug, Clone)]
 pub struct Token {
     token_type: TokenType,
-    lexeme: String,
-    line: i32,
+    // Interned, identifiers are looked up by it at runtime
+    lexeme: Symbol,
     literal: Object,
+    span: Span,
 }
 
-impl Token {
-    pub fn to_string(&self) -> String {
-        return format!("{:?} {} {}", self.token_type, self.lexeme, self.line);
+impl fmt::Display for Token {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(
+            f,
+            "{:?} {} {}",
+            self.token_type, self.lexeme, self.span.line
+        )
     }
+}
 
+impl Token {
     pub fn print(&self) -> String {
-        format!("{}", self.lexeme)
+        self.lexeme.to_string()
     }
 
     pub fn get_type(&self) -> TokenType {
         self.token_type.clone()
     }
 
-    pub fn get_line(&self) -> i32 {
-        self.line
+    pub fn get_span(&self) -> Span {
+        self.span
     }
 
     pub fn get_lexeme(&self) -> String {
+        self.lexeme.to_string()
+    }
+
+    pub fn get_name(&self) -> Symbol {
         self.lexeme.clone()
     }
 
-    pub fn new(token_type: TokenType, lexeme: String, line: i32, object: Object) -> Token {
-        return Token {
+    pub fn get_literal(&self) -> Object {
+        self.literal.clone()
+    }
+
+    pub fn new(token_type: TokenType, lexeme: String, object: Object, span: Span) -> Token {
+        Token {
             token_type,
-            lexeme,
-            line,
+            lexeme: Symbol::from(lexeme),
             literal: object,
-        };
+            span,
+        }
     }
 }
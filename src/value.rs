@@ -0,0 +1,175 @@
+use crate::callable::LoxCallable;
+use crate::class::{LoxClass, LoxInstance};
+use crate::intern::Symbol;
+use std::cell::RefCell;
+use std::fmt;
+use std::rc::Rc;
+
+// A runtime value. Heap values are shared, so cloning one hands out another reference
+#[derive(Clone)]
+pub enum Value {
+    Nil,
+    Bool(bool),
+    Number(f64),
+    String(Symbol),
+    Callable(Callable),
+    Class(Rc<LoxClass>),
+    Instance(Rc<RefCell<LoxInstance>>),
+    // A function, class or instance of the VM. Host code can print it but not pass it back in
+    VmObject(VmObject),
+}
+
+// A function, method or native as seen by host code, which can only ask for its name and arity
+#[derive(Clone)]
+pub struct Callable(Rc<dyn LoxCallable>);
+
+impl Callable {
+    pub(crate) fn new(function: Rc<dyn LoxCallable>) -> Callable {
+        Callable(function)
+    }
+
+    pub(crate) fn get(&self) -> &dyn LoxCallable {
+        self.0.as_ref()
+    }
+
+    pub(crate) fn share(&self) -> Rc<dyn LoxCallable> {
+        Rc::clone(&self.0)
+    }
+
+    pub fn name(&self) -> String {
+        self.0.name()
+    }
+
+    pub fn arity(&self) -> usize {
+        self.0.arity()
+    }
+}
+
+// What `print` would show for a VM object, the object itself stays in the VM's heap
+#[derive(Clone)]
+pub struct VmObject(String);
+
+impl VmObject {
+    pub(crate) fn new(description: String) -> VmObject {
+        VmObject(description)
+    }
+}
+
+impl fmt::Display for Value {
+    // Formats values the way `print` shows them
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        match self {
+            Value::Nil => write!(f, "nil"),
+            Value::Bool(b) => write!(f, "{}", b),
+            Value::Number(num) => {
+                let mut num_str = num.to_string();
+                if num_str.ends_with(".0") {
+                    num_str.truncate(num_str.len() - 2);
+                }
+                write!(f, "{}", num_str)
+            }
+            Value::String(str) => write!(f, "{}", str),
+            Value::Callable(callable) => write!(f, "<fn {}>", callable.name()),
+            Value::Class(class) => write!(f, "{}", class.name()),
+            Value::Instance(instance) => {
+                write!(f, "{} instance", instance.borrow().class_name())
+            }
+            Value::VmObject(object) => write!(f, "{}", object.0),
+        }
+    }
+}
+
+impl fmt::Debug for Value {
+    // Same as Display, except strings are quoted so `"nil"` and `nil` can be told apart
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        match self {
+            Value::String(str) => write!(f, "{:?}", str.to_string()),
+            val => write!(f, "{}", val),
+        }
+    }
+}
+
+impl PartialEq for Value {
+    // Lox equality, as used by `==`
+    fn eq(&self, other: &Value) -> bool {
+        match (self, other) {
+            (Value::Nil, Value::Nil) => true,
+            (Value::Bool(left), Value::Bool(right)) => left == right,
+            (Value::Number(left), Value::Number(right)) => left == right,
+            (Value::String(left), Value::String(right)) => left == right,
+            // Heap values are only equal to themselves, VM objects can't be told apart
+            (Value::Callable(left), Value::Callable(right)) => Rc::ptr_eq(&left.0, &right.0),
+            (Value::Class(left), Value::Class(right)) => Rc::ptr_eq(left, right),
+            (Value::Instance(left), Value::Instance(right)) => Rc::ptr_eq(left, right),
+            _ => false,
+        }
+    }
+}
+
+// Conversions for host code passing values into and out of scripts
+
+impl From<f64> for Value {
+    fn from(num: f64) -> Value {
+        Value::Number(num)
+    }
+}
+
+impl From<bool> for Value {
+    fn from(b: bool) -> Value {
+        Value::Bool(b)
+    }
+}
+
+impl From<String> for Value {
+    fn from(str: String) -> Value {
+        Value::String(Symbol::from(str))
+    }
+}
+
+impl From<&str> for Value {
+    fn from(str: &str) -> Value {
+        Value::String(Symbol::from(str))
+    }
+}
+
+impl<T: Into<Value>> From<Option<T>> for Value {
+    fn from(val: Option<T>) -> Value {
+        match val {
+            Some(val) => val.into(),
+            None => Value::Nil,
+        }
+    }
+}
+
+impl TryFrom<Value> for f64 {
+    type Error = String;
+
+    fn try_from(val: Value) -> Result<f64, String> {
+        match val {
+            Value::Number(num) => Ok(num),
+            _ => Err("Expected a number".to_string()),
+        }
+    }
+}
+
+impl TryFrom<Value> for bool {
+    type Error = String;
+
+    fn try_from(val: Value) -> Result<bool, String> {
+        match val {
+            Value::Bool(b) => Ok(b),
+            _ => Err("Expected a boolean".to_string()),
+        }
+    }
+}
+
+impl TryFrom<Value> for String {
+    type Error = String;
+
+    fn try_from(val: Value) -> Result<String, String> {
+        match val {
+            Value::String(str) => Ok(str.to_string()),
+            _ => Err("Expected a string".to_string()),
+        }
+    }
+}
@@ -0,0 +1,206 @@
+use crate::token::Span;
+use crate::vm::object::VmValue;
+
+// One byte per instruction, followed by its operands. Constant indexes take two bytes, high
+// byte first
+#[derive(Clone, Copy, Debug, PartialEq)]
+#[repr(u8)]
+pub enum OpCode {
+    // Operands: constant index
+    Constant,
+    Nil,
+    True,
+    False,
+    Pop,
+    // Operands: stack slot relative to the frame
+    GetLocal,
+    SetLocal,
+    // Operands: constant index of the name
+    GetGlobal,
+    DefineGlobal,
+    SetGlobal,
+    // Operands: index into the closure's upvalues
+    GetUpvalue,
+    SetUpvalue,
+    // Operands: constant index of the name
+    GetProperty,
+    SetProperty,
+    GetSuper,
+    Equal,
+    Greater,
+    Less,
+    Add,
+    Subtract,
+    Multiply,
+    Divide,
+    Not,
+    Negate,
+    Print,
+    // Operands: 16 bit forward offset
+    Jump,
+    JumpIfFalse,
+    // Operands: 16 bit backward offset
+    Loop,
+    // Operands: argument count
+    Call,
+    // Operands: constant index of the method name, argument count
+    Invoke,
+    SuperInvoke,
+    // Operands: constant index of the function, then a pair of bytes per upvalue
+    Closure,
+    CloseUpvalue,
+    Return,
+    // Operands: constant index of the name
+    Class,
+    Inherit,
+    Method,
+}
+
+impl OpCode {
+    pub fn from_byte(byte: u8) -> OpCode {
+        match byte {
+            0 => OpCode::Constant,
+            1 => OpCode::Nil,
+            2 => OpCode::True,
+            3 => OpCode::False,
+            4 => OpCode::Pop,
+            5 => OpCode::GetLocal,
+            6 => OpCode::SetLocal,
+            7 => OpCode::GetGlobal,
+            8 => OpCode::DefineGlobal,
+            9 => OpCode::SetGlobal,
+            10 => OpCode::GetUpvalue,
+            11 => OpCode::SetUpvalue,
+            12 => OpCode::GetProperty,
+            13 => OpCode::SetProperty,
+            14 => OpCode::GetSuper,
+            15 => OpCode::Equal,
+            16 => OpCode::Greater,
+            17 => OpCode::Less,
+            18 => OpCode::Add,
+            19 => OpCode::Subtract,
+            20 => OpCode::Multiply,
+            21 => OpCode::Divide,
+            22 => OpCode::Not,
+            23 => OpCode::Negate,
+            24 => OpCode::Print,
+            25 => OpCode::Jump,
+            26 => OpCode::JumpIfFalse,
+            27 => OpCode::Loop,
+            28 => OpCode::Call,
+            29 => OpCode::Invoke,
+            30 => OpCode::SuperInvoke,
+            31 => OpCode::Closure,
+            32 => OpCode::CloseUpvalue,
+            33 => OpCode::Return,
+            34 => OpCode::Class,
+            35 => OpCode::Inherit,
+            36 => OpCode::Method,
+            // The compiler only ever writes the opcodes above
+            _ => unreachable!(),
+        }
+    }
+
+    // Name shown when disassembling
+    pub fn name(&self) -> &'static str {
+        match self {
+            OpCode::Constant => "OP_CONSTANT",
+            OpCode::Nil => "OP_NIL",
+            OpCode::True => "OP_TRUE",
+            OpCode::False => "OP_FALSE",
+            OpCode::Pop => "OP_POP",
+            OpCode::GetLocal => "OP_GET_LOCAL",
+            OpCode::SetLocal => "OP_SET_LOCAL",
+            OpCode::GetGlobal => "OP_GET_GLOBAL",
+            OpCode::DefineGlobal => "OP_DEFINE_GLOBAL",
+            OpCode::SetGlobal => "OP_SET_GLOBAL",
+            OpCode::GetUpvalue => "OP_GET_UPVALUE",
+            OpCode::SetUpvalue => "OP_SET_UPVALUE",
+            OpCode::GetProperty => "OP_GET_PROPERTY",
+            OpCode::SetProperty => "OP_SET_PROPERTY",
+            OpCode::GetSuper => "OP_GET_SUPER",
+            OpCode::Equal => "OP_EQUAL",
+            OpCode::Greater => "OP_GREATER",
+            OpCode::Less => "OP_LESS",
+            OpCode::Add => "OP_ADD",
+            OpCode::Subtract => "OP_SUBTRACT",
+            OpCode::Multiply => "OP_MULTIPLY",
+            OpCode::Divide => "OP_DIVIDE",
+            OpCode::Not => "OP_NOT",
+            OpCode::Negate => "OP_NEGATE",
+            OpCode::Print => "OP_PRINT",
+            OpCode::Jump => "OP_JUMP",
+            OpCode::JumpIfFalse => "OP_JUMP_IF_FALSE",
+            OpCode::Loop => "OP_LOOP",
+            OpCode::Call => "OP_CALL",
+            OpCode::Invoke => "OP_INVOKE",
+            OpCode::SuperInvoke => "OP_SUPER_INVOKE",
+            OpCode::Closure => "OP_CLOSURE",
+            OpCode::CloseUpvalue => "OP_CLOSE_UPVALUE",
+            OpCode::Return => "OP_RETURN",
+            OpCode::Class => "OP_CLASS",
+            OpCode::Inherit => "OP_INHERIT",
+            OpCode::Method => "OP_METHOD",
+        }
+    }
+}
+
+// A compiled function body
+#[derive(Debug, Default)]
+pub struct Chunk {
+    code: Vec<u8>,
+    constants: Vec<VmValue>,
+    // Run length encoded, each entry is the offset where a new source span starts
+    spans: Vec<(usize, Span)>,
+}
+
+impl Chunk {
+    pub fn new() -> Chunk {
+        Chunk {
+            ..Default::default()
+        }
+    }
+
+    pub fn write(&mut self, byte: u8, span: Span) {
+        if self.spans.last().map(|(_, last)| *last) != Some(span) {
+            self.spans.push((self.code.len(), span));
+        }
+        self.code.push(byte);
+    }
+
+    // Overwrites an operand that was written before its value was known
+    pub fn patch(&mut self, offset: usize, byte: u8) {
+        self.code[offset] = byte;
+    }
+
+    // Returns the index of the new constant
+    pub fn add_constant(&mut self, value: VmValue) -> usize {
+        self.constants.push(value);
+        self.constants.len() - 1
+    }
+
+    pub fn len(&self) -> usize {
+        self.code.len()
+    }
+
+    pub fn get_byte(&self, offset: usize) -> u8 {
+        self.code[offset]
+    }
+
+    pub fn constant_count(&self) -> usize {
+        self.constants.len()
+    }
+
+    pub fn get_constant(&self, index: usize) -> VmValue {
+        self.constants[index]
+    }
+
+    // Source span of the instruction at `offset`
+    pub fn get_span(&self, offset: usize) -> Span {
+        let index: usize = self.spans.partition_point(|(start, _)| *start <= offset);
+        match index.checked_sub(1) {
+            Some(index) => self.spans[index].1,
+            None => Span::default(),
+        }
+    }
+}
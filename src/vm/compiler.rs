@@ -0,0 +1,695 @@
+use crate::expr::{
+    AssignmentExpression, BinaryExpression, CallExpression, Expr, GetExpression, LogicalExpression,
+    SetExpression, SuperExpression, UnaryExpression,
+};
+use crate::intern::Symbol;
+use crate::stmt::{
+    BlockStatement, ClassStatement, FunctionStatement, IfStatement, ReturnStatement, Stmt, VarStmt,
+    WhileStatement,
+};
+use crate::token::{Span, Token};
+use crate::token_type::TokenType;
+use crate::value::Value;
+use crate::vm::chunk::{Chunk, OpCode};
+use crate::vm::object::{Function, Heap, ObjRef, Object, VmValue};
+use crate::Rlox;
+use std::collections::HashMap;
+use std::rc::Rc;
+
+#[derive(Clone, Copy, PartialEq)]
+enum FunctionType {
+    Script,
+    Function,
+    Initializer,
+    Method,
+}
+
+struct Local {
+    // Slot zero of a plain function can't be referred to by name
+    name: Option<Symbol>,
+    depth: usize,
+    // Captured locals are moved to the heap rather than popped when they go out of scope
+    is_captured: bool,
+}
+
+#[derive(Clone, Copy, PartialEq)]
+struct UpvalueRef {
+    // Slot in the enclosing function if `is_local`, otherwise one of its upvalues
+    index: u8,
+    is_local: bool,
+}
+
+// A function whose body is being compiled
+struct FunctionState {
+    function_type: FunctionType,
+    name: Option<String>,
+    arity: usize,
+    chunk: Chunk,
+    locals: Vec<Local>,
+    upvalues: Vec<UpvalueRef>,
+    scope_depth: usize,
+    // Constants already in the chunk, so each literal and name is only stored once
+    constants: HashMap<ConstantKey, u16>,
+}
+
+// Identifies a constant for deduplication. Numbers go by their bits so `0` and `-0` stay
+// apart, strings are interned so equal ones share an object
+#[derive(Clone, Copy, PartialEq, Eq, Hash)]
+enum ConstantKey {
+    Number(u64),
+    Obj(ObjRef),
+}
+
+impl FunctionState {
+    fn new(
+        function_type: FunctionType,
+        name: Option<String>,
+        slot_zero: Option<Symbol>,
+    ) -> FunctionState {
+        FunctionState {
+            function_type,
+            name,
+            arity: 0,
+            chunk: Chunk::new(),
+            locals: vec![Local {
+                name: slot_zero,
+                depth: 0,
+                is_captured: false,
+            }],
+            upvalues: Vec::new(),
+            scope_depth: 0,
+            constants: HashMap::new(),
+        }
+    }
+}
+
+// Turns a resolved program into bytecode. The resolver has already rejected invalid
+// programs, so the only errors left are the VM's limits
+pub struct Compiler<'a> {
+    functions: Vec<FunctionState>,
+    heap: &'a mut Heap,
+    rlox: &'a mut Rlox,
+    this_string: Symbol,
+    super_string: Symbol,
+    init_string: Symbol,
+}
+
+impl Compiler<'_> {
+    pub fn new<'a>(rlox: &'a mut Rlox, heap: &'a mut Heap) -> Compiler<'a> {
+        Compiler {
+            functions: Vec::new(),
+            heap,
+            rlox,
+            this_string: Symbol::intern("this"),
+            super_string: Symbol::intern("super"),
+            init_string: Symbol::intern("init"),
+        }
+    }
+
+    // Returns the top level function of the program
+    pub fn compile(mut self, stmts: &[Stmt]) -> ObjRef {
+        self.begin_function(FunctionType::Script, None);
+
+        let count: usize = stmts.len();
+        let mut end: Span = Span::default();
+        for (index, stmt) in stmts.iter().enumerate() {
+            end = stmt.get_span();
+            match stmt {
+                // A trailing expression is the program's value
+                Stmt::Expression(stmt) if index == count - 1 => {
+                    self.compile_expr(Stmt::get_expr_stmt_expr(stmt));
+                    self.emit_op(OpCode::Return, end);
+                    return self.end_function();
+                }
+                stmt => self.compile_stmt(stmt),
+            }
+        }
+        self.emit_return(end);
+        self.end_function()
+    }
+
+    fn compile_stmts(&mut self, stmts: &[Stmt]) {
+        for stmt in stmts {
+            self.compile_stmt(stmt);
+        }
+    }
+
+    fn compile_stmt(&mut self, stmt: &Stmt) {
+        let span: Span = stmt.get_span();
+        match stmt {
+            Stmt::Block(stmt) => self.visit_block_stmt(stmt, span),
+            Stmt::Class(stmt) => self.visit_class_stmt(stmt),
+            Stmt::Expression(stmt) => {
+                self.compile_expr(Stmt::get_expr_stmt_expr(stmt));
+                self.emit_op(OpCode::Pop, span);
+            }
+            Stmt::Function(stmt) => self.visit_function_stmt(stmt),
+            Stmt::If(stmt) => self.visit_if_stmt(stmt),
+            Stmt::Print(stmt) => {
+                self.compile_expr(Stmt::get_print_stmt_expr(stmt));
+                self.emit_op(OpCode::Print, span);
+            }
+            Stmt::Return(stmt) => self.visit_return_stmt(stmt),
+            Stmt::Var(stmt) => self.visit_var_stmt(stmt),
+            Stmt::While(stmt) => self.visit_while_stmt(stmt),
+        }
+    }
+
+    fn compile_expr(&mut self, expr: &Expr) {
+        let span: Span = expr.get_span();
+        match expr {
+            Expr::Assign(expr) => self.visit_assign_expr(expr),
+            Expr::Binary(expr) => self.visit_binary_expr(expr),
+            Expr::Call(expr) => self.visit_call_expr(expr),
+            Expr::Get(expr) => self.visit_get_expr(expr),
+            Expr::Grouping(expr) => self.compile_expr(Expr::get_grouping_expr(expr)),
+            Expr::Literal(expr) => self.literal(Expr::get_literal_value(expr), span),
+            Expr::Logical(expr) => self.visit_logical_expr(expr),
+            Expr::Set(expr) => self.visit_set_expr(expr),
+            Expr::Super(expr) => self.visit_super_expr(expr),
+            Expr::This(_) => self.named_variable(&self.this_string.clone(), span, None),
+            Expr::Unary(expr) => self.visit_unary_expr(expr),
+            Expr::Variable(expr) => {
+                let name: &Token = Expr::get_var_name(expr);
+                self.named_variable(&name.get_name(), name.get_span(), None);
+            }
+        }
+    }
+
+    fn current(&mut self) -> &mut FunctionState {
+        self.functions
+            .last_mut()
+            .expect("Compiler must be inside a function")
+    }
+
+    fn emit_byte(&mut self, byte: u8, span: Span) {
+        self.current().chunk.write(byte, span);
+    }
+
+    fn emit_op(&mut self, op: OpCode, span: Span) {
+        self.emit_byte(op as u8, span);
+    }
+
+    fn emit_op_operand(&mut self, op: OpCode, operand: u8, span: Span) {
+        self.emit_op(op, span);
+        self.emit_byte(operand, span);
+    }
+
+    fn emit_constant_op(&mut self, op: OpCode, constant: u16, span: Span) {
+        self.emit_op(op, span);
+        self.emit_byte((constant >> 8) as u8, span);
+        self.emit_byte(constant as u8, span);
+    }
+
+    fn emit_return(&mut self, span: Span) {
+        // Initializers always hand back the new instance
+        if self.current().function_type == FunctionType::Initializer {
+            self.emit_op_operand(OpCode::GetLocal, 0, span);
+        } else {
+            self.emit_op(OpCode::Nil, span);
+        }
+        self.emit_op(OpCode::Return, span);
+    }
+
+    // Emits a jump with a placeholder offset, returning where to patch it
+    fn emit_jump(&mut self, op: OpCode, span: Span) -> usize {
+        self.emit_op(op, span);
+        self.emit_byte(0xff, span);
+        self.emit_byte(0xff, span);
+        self.current().chunk.len() - 2
+    }
+
+    fn patch_jump(&mut self, offset: usize, span: Span) {
+        // Skip over the offset itself
+        let jump: usize = self.current().chunk.len() - offset - 2;
+        if jump > u16::MAX as usize {
+            self.rlox.compile_error(span, "Too much code to jump over");
+        }
+        let chunk: &mut Chunk = &mut self.current().chunk;
+        chunk.patch(offset, (jump >> 8) as u8);
+        chunk.patch(offset + 1, jump as u8);
+    }
+
+    fn emit_loop(&mut self, loop_start: usize, span: Span) {
+        self.emit_op(OpCode::Loop, span);
+        let offset: usize = self.current().chunk.len() - loop_start + 2;
+        if offset > u16::MAX as usize {
+            self.rlox.compile_error(span, "Loop body too large");
+        }
+        self.emit_byte((offset >> 8) as u8, span);
+        self.emit_byte(offset as u8, span);
+    }
+
+    fn make_constant(&mut self, value: VmValue, span: Span) -> u16 {
+        let key: ConstantKey = match value {
+            VmValue::Number(num) => ConstantKey::Number(num.to_bits()),
+            VmValue::Obj(obj) => ConstantKey::Obj(obj),
+            // Nil and booleans have their own instructions
+            _ => unreachable!(),
+        };
+        if let Some(index) = self.current().constants.get(&key) {
+            return *index;
+        }
+        let index: usize = self.current().chunk.add_constant(value);
+        match u16::try_from(index) {
+            Ok(index) => {
+                self.current().constants.insert(key, index);
+                index
+            }
+            Err(_) => {
+                // Only the first constant over the limit is reported
+                if index == u16::MAX as usize + 1 {
+                    self.rlox
+                        .compile_error(span, "Too many constants in one chunk");
+                }
+                0
+            }
+        }
+    }
+
+    fn identifier_constant(&mut self, name: &Symbol, span: Span) -> u16 {
+        let value: VmValue = self.heap.alloc_string(name.clone());
+        self.make_constant(value, span)
+    }
+
+    fn begin_scope(&mut self) {
+        self.current().scope_depth += 1;
+    }
+
+    fn end_scope(&mut self, span: Span) {
+        self.current().scope_depth -= 1;
+        loop {
+            let state: &mut FunctionState = self.current();
+            let is_captured: bool = match state.locals.last() {
+                Some(local) if local.depth > state.scope_depth => local.is_captured,
+                _ => break,
+            };
+            state.locals.pop();
+            if is_captured {
+                self.emit_op(OpCode::CloseUpvalue, span);
+            } else {
+                self.emit_op(OpCode::Pop, span);
+            }
+        }
+    }
+
+    fn add_local(&mut self, name: Symbol, span: Span) {
+        if self.current().locals.len() > u8::MAX as usize {
+            self.rlox
+                .compile_error(span, "Too many local variables in function");
+            return;
+        }
+        let depth: usize = self.current().scope_depth;
+        self.current().locals.push(Local {
+            name: Some(name),
+            depth,
+            is_captured: false,
+        });
+    }
+
+    fn resolve_local(&self, function: usize, name: &Symbol) -> Option<u8> {
+        self.functions[function]
+            .locals
+            .iter()
+            .rposition(|local| local.name.as_ref() == Some(name))
+            .map(|slot| slot as u8)
+    }
+
+    // Finds a variable captured from an enclosing function, threading it through every
+    // function in between
+    fn resolve_upvalue(&mut self, function: usize, name: &Symbol, span: Span) -> Option<u8> {
+        let enclosing: usize = function.checked_sub(1)?;
+        if let Some(slot) = self.resolve_local(enclosing, name) {
+            self.functions[enclosing].locals[slot as usize].is_captured = true;
+            return Some(self.add_upvalue(function, slot, true, span));
+        }
+        let index: u8 = self.resolve_upvalue(enclosing, name, span)?;
+        Some(self.add_upvalue(function, index, false, span))
+    }
+
+    fn add_upvalue(&mut self, function: usize, index: u8, is_local: bool, span: Span) -> u8 {
+        let upvalue = UpvalueRef { index, is_local };
+        let upvalues: &mut Vec<UpvalueRef> = &mut self.functions[function].upvalues;
+        if let Some(existing) = upvalues.iter().position(|u| *u == upvalue) {
+            return existing as u8;
+        }
+        if upvalues.len() > u8::MAX as usize {
+            self.rlox
+                .compile_error(span, "Too many closure variables in function");
+            return 0;
+        }
+        upvalues.push(upvalue);
+        (upvalues.len() - 1) as u8
+    }
+
+    // Loads a variable, or stores `val` in it when given one
+    fn named_variable(&mut self, name: &Symbol, span: Span, val: Option<&Expr>) {
+        let function: usize = self.functions.len() - 1;
+        let (get_op, set_op, operand) = if let Some(slot) = self.resolve_local(function, name) {
+            (OpCode::GetLocal, OpCode::SetLocal, slot as u16)
+        } else if let Some(index) = self.resolve_upvalue(function, name, span) {
+            (OpCode::GetUpvalue, OpCode::SetUpvalue, index as u16)
+        } else {
+            let constant: u16 = self.identifier_constant(name, span);
+            (OpCode::GetGlobal, OpCode::SetGlobal, constant)
+        };
+
+        let op: OpCode = match val {
+            Some(val) => {
+                self.compile_expr(val);
+                set_op
+            }
+            None => get_op,
+        };
+        // Globals are named by a constant, the other two by a single byte slot
+        if op == OpCode::GetGlobal || op == OpCode::SetGlobal {
+            self.emit_constant_op(op, operand, span);
+        } else {
+            self.emit_op_operand(op, operand as u8, span);
+        }
+    }
+
+    // Binds the value on top of the stack to a new variable
+    fn define_variable(&mut self, name: &Token) {
+        if self.current().scope_depth > 0 {
+            // Locals simply stay where they are on the stack
+            self.add_local(name.get_name(), name.get_span());
+        } else {
+            let constant: u16 = self.identifier_constant(&name.get_name(), name.get_span());
+            self.emit_constant_op(OpCode::DefineGlobal, constant, name.get_span());
+        }
+    }
+
+    fn function(&mut self, stmt: &FunctionStatement, function_type: FunctionType) {
+        let name: &Token = Stmt::get_function_name(stmt);
+        let span: Span = name.get_span();
+        self.begin_function(function_type, Some(name.get_lexeme()));
+        // No matching end_scope, returning discards the whole frame
+        self.begin_scope();
+
+        for param in Stmt::get_function_params(stmt) {
+            self.current().arity += 1;
+            self.add_local(param.get_name(), param.get_span());
+        }
+        self.compile_stmts(Stmt::get_function_body(stmt));
+        self.emit_return(span);
+
+        let upvalues: Vec<UpvalueRef> = self.current().upvalues.clone();
+        let function: ObjRef = self.end_function();
+        let constant: u16 = self.make_constant(VmValue::Obj(function), span);
+        self.emit_constant_op(OpCode::Closure, constant, span);
+        for upvalue in upvalues {
+            self.emit_byte(upvalue.is_local as u8, span);
+            self.emit_byte(upvalue.index, span);
+        }
+    }
+
+    fn begin_function(&mut self, function_type: FunctionType, name: Option<String>) {
+        // Slot zero holds the function being called, or the receiver inside methods
+        let slot_zero: Option<Symbol> = match function_type {
+            FunctionType::Initializer | FunctionType::Method => Some(self.this_string.clone()),
+            FunctionType::Function | FunctionType::Script => None,
+        };
+        self.functions
+            .push(FunctionState::new(function_type, name, slot_zero));
+    }
+
+    // Moves the innermost function into the heap
+    fn end_function(&mut self) -> ObjRef {
+        let state: FunctionState = self
+            .functions
+            .pop()
+            .expect("Compiler must be inside a function");
+        self.heap.alloc(Object::Function(Function {
+            name: state.name,
+            arity: state.arity,
+            upvalue_count: state.upvalues.len(),
+            chunk: Rc::new(state.chunk),
+        }))
+    }
+
+    fn literal(&mut self, value: &Value, span: Span) {
+        match value {
+            Value::Nil => self.emit_op(OpCode::Nil, span),
+            Value::Bool(true) => self.emit_op(OpCode::True, span),
+            Value::Bool(false) => self.emit_op(OpCode::False, span),
+            Value::Number(num) => {
+                let constant: u16 = self.make_constant(VmValue::Number(*num), span);
+                self.emit_constant_op(OpCode::Constant, constant, span);
+            }
+            Value::String(str) => {
+                let value: VmValue = self.heap.alloc_string(str.clone());
+                let constant: u16 = self.make_constant(value, span);
+                self.emit_constant_op(OpCode::Constant, constant, span);
+            }
+            // The parser only creates literals from tokens
+            _ => unreachable!(),
+        }
+    }
+
+    fn visit_block_stmt(&mut self, stmt: &BlockStatement, span: Span) {
+        self.begin_scope();
+        self.compile_stmts(Stmt::get_block_stmts(stmt));
+        self.end_scope(span);
+    }
+
+    fn visit_class_stmt(&mut self, stmt: &ClassStatement) {
+        let name: &Token = Stmt::get_class_name(stmt);
+        let span: Span = name.get_span();
+        let constant: u16 = self.identifier_constant(&name.get_name(), span);
+        self.emit_constant_op(OpCode::Class, constant, span);
+        self.define_variable(name);
+
+        let superclass: Option<&Expr> = Stmt::get_class_superclass(stmt);
+        let has_superclass: bool = superclass.is_some();
+        if let Some(superclass) = superclass {
+            let superclass_span: Span = superclass.get_span();
+            self.compile_expr(superclass);
+            // Methods capture the superclass through a scope binding `super`
+            self.begin_scope();
+            self.add_local(self.super_string.clone(), superclass_span);
+            self.named_variable(&name.get_name(), span, None);
+            self.emit_op(OpCode::Inherit, superclass_span);
+        }
+
+        // Keep the class on the stack while its methods are attached
+        self.named_variable(&name.get_name(), span, None);
+        for method in Stmt::get_class_methods(stmt) {
+            let method_name: &Token = Stmt::get_function_name(method);
+            let constant: u16 =
+                self.identifier_constant(&method_name.get_name(), method_name.get_span());
+            let function_type: FunctionType = if method_name.get_name() == self.init_string {
+                FunctionType::Initializer
+            } else {
+                FunctionType::Method
+            };
+            self.function(method, function_type);
+            self.emit_constant_op(OpCode::Method, constant, method_name.get_span());
+        }
+        self.emit_op(OpCode::Pop, span);
+
+        if has_superclass {
+            self.end_scope(span);
+        }
+    }
+
+    fn visit_function_stmt(&mut self, stmt: &FunctionStatement) {
+        let name: &Token = Stmt::get_function_name(stmt);
+        // Declare local functions first so they can call themselves
+        if self.current().scope_depth > 0 {
+            self.add_local(name.get_name(), name.get_span());
+            self.function(stmt, FunctionType::Function);
+        } else {
+            self.function(stmt, FunctionType::Function);
+            self.define_variable(name);
+        }
+    }
+
+    fn visit_if_stmt(&mut self, stmt: &IfStatement) {
+        let condition: &Expr = Stmt::get_if_condition(stmt);
+        let span: Span = condition.get_span();
+        self.compile_expr(condition);
+
+        let then_jump: usize = self.emit_jump(OpCode::JumpIfFalse, span);
+        self.emit_op(OpCode::Pop, span);
+        self.compile_stmt(Stmt::get_if_then(stmt));
+        let else_jump: usize = self.emit_jump(OpCode::Jump, span);
+
+        self.patch_jump(then_jump, span);
+        self.emit_op(OpCode::Pop, span);
+        if let Some(else_branch) = Stmt::get_if_else(stmt) {
+            self.compile_stmt(else_branch);
+        }
+        self.patch_jump(else_jump, span);
+    }
+
+    fn visit_return_stmt(&mut self, stmt: &ReturnStatement) {
+        let span: Span = Stmt::get_return_keyword(stmt).get_span();
+        match Stmt::get_return_value(stmt) {
+            Some(value) => {
+                self.compile_expr(value);
+                self.emit_op(OpCode::Return, span);
+            }
+            None => self.emit_return(span),
+        }
+    }
+
+    fn visit_var_stmt(&mut self, stmt: &VarStmt) {
+        let name: &Token = Stmt::get_var_name(stmt);
+        match Stmt::get_var_initializer(stmt) {
+            Some(initializer) => self.compile_expr(initializer),
+            None => self.emit_op(OpCode::Nil, name.get_span()),
+        }
+        self.define_variable(name);
+    }
+
+    fn visit_while_stmt(&mut self, stmt: &WhileStatement) {
+        let loop_start: usize = self.current().chunk.len();
+        let condition: &Expr = Stmt::get_while_condition(stmt);
+        let span: Span = condition.get_span();
+        self.compile_expr(condition);
+
+        let exit_jump: usize = self.emit_jump(OpCode::JumpIfFalse, span);
+        self.emit_op(OpCode::Pop, span);
+        self.compile_stmt(Stmt::get_while_body(stmt));
+        self.emit_loop(loop_start, span);
+
+        self.patch_jump(exit_jump, span);
+        self.emit_op(OpCode::Pop, span);
+    }
+
+    fn visit_assign_expr(&mut self, expr: &AssignmentExpression) {
+        let name: &Token = Expr::get_assign_name(expr);
+        let val: &Expr = Expr::get_assign_val(expr);
+        self.named_variable(&name.get_name(), name.get_span(), Some(val));
+    }
+
+    fn visit_binary_expr(&mut self, expr: &BinaryExpression) {
+        self.compile_expr(Expr::get_binary_left(expr));
+        self.compile_expr(Expr::get_binary_right(expr));
+
+        let span: Span = Expr::get_binary_operator(expr).get_span();
+        match Expr::get_binary_op(expr) {
+            TokenType::BangEqual => {
+                self.emit_op(OpCode::Equal, span);
+                self.emit_op(OpCode::Not, span);
+            }
+            TokenType::EqualEqual => self.emit_op(OpCode::Equal, span),
+            TokenType::Greater => self.emit_op(OpCode::Greater, span),
+            TokenType::GreaterEqual => {
+                self.emit_op(OpCode::Less, span);
+                self.emit_op(OpCode::Not, span);
+            }
+            TokenType::Less => self.emit_op(OpCode::Less, span),
+            TokenType::LessEqual => {
+                self.emit_op(OpCode::Greater, span);
+                self.emit_op(OpCode::Not, span);
+            }
+            TokenType::Plus => self.emit_op(OpCode::Add, span),
+            TokenType::Minus => self.emit_op(OpCode::Subtract, span),
+            TokenType::Star => self.emit_op(OpCode::Multiply, span),
+            TokenType::Slash => self.emit_op(OpCode::Divide, span),
+            // There should not be any other types of operations in Binary Expressions
+            _ => unreachable!(),
+        }
+    }
+
+    fn visit_call_expr(&mut self, expr: &CallExpression) {
+        let span: Span = Expr::get_call_paren(expr).get_span();
+        let args: &[Expr] = Expr::get_call_args(expr);
+        let arg_count: u8 = args.len() as u8;
+
+        // Calling a method directly skips creating a bound method
+        match Expr::get_call_callee(expr) {
+            Expr::Get(get) => {
+                let name: &Token = Expr::get_get_name(get);
+                self.compile_expr(Expr::get_get_object(get));
+                for arg in args {
+                    self.compile_expr(arg);
+                }
+                let constant: u16 = self.identifier_constant(&name.get_name(), name.get_span());
+                self.emit_constant_op(OpCode::Invoke, constant, span);
+                self.emit_byte(arg_count, span);
+            }
+            Expr::Super(expr) => {
+                let method: &Token = Expr::get_super_method(expr);
+                self.named_variable(&self.this_string.clone(), method.get_span(), None);
+                for arg in args {
+                    self.compile_expr(arg);
+                }
+                self.named_variable(&self.super_string.clone(), method.get_span(), None);
+                let constant: u16 = self.identifier_constant(&method.get_name(), method.get_span());
+                self.emit_constant_op(OpCode::SuperInvoke, constant, span);
+                self.emit_byte(arg_count, span);
+            }
+            callee => {
+                self.compile_expr(callee);
+                for arg in args {
+                    self.compile_expr(arg);
+                }
+                self.emit_op_operand(OpCode::Call, arg_count, span);
+            }
+        }
+    }
+
+    fn visit_get_expr(&mut self, expr: &GetExpression) {
+        let name: &Token = Expr::get_get_name(expr);
+        self.compile_expr(Expr::get_get_object(expr));
+        let constant: u16 = self.identifier_constant(&name.get_name(), name.get_span());
+        self.emit_constant_op(OpCode::GetProperty, constant, name.get_span());
+    }
+
+    fn visit_logical_expr(&mut self, expr: &LogicalExpression) {
+        let left: &Expr = Expr::get_logical_left(expr);
+        let span: Span = left.get_span();
+        self.compile_expr(left);
+
+        // Short-circuit, leaving the deciding operand on the stack
+        match Expr::get_logical_op(expr) {
+            TokenType::And => {
+                let end_jump: usize = self.emit_jump(OpCode::JumpIfFalse, span);
+                self.emit_op(OpCode::Pop, span);
+                self.compile_expr(Expr::get_logical_right(expr));
+                self.patch_jump(end_jump, span);
+            }
+            TokenType::Or => {
+                let else_jump: usize = self.emit_jump(OpCode::JumpIfFalse, span);
+                let end_jump: usize = self.emit_jump(OpCode::Jump, span);
+                self.patch_jump(else_jump, span);
+                self.emit_op(OpCode::Pop, span);
+                self.compile_expr(Expr::get_logical_right(expr));
+                self.patch_jump(end_jump, span);
+            }
+            // There should not be any other types of operations in Logical Expressions
+            _ => unreachable!(),
+        }
+    }
+
+    fn visit_set_expr(&mut self, expr: &SetExpression) {
+        let name: &Token = Expr::get_set_name(expr);
+        self.compile_expr(Expr::get_set_object(expr));
+        self.compile_expr(Expr::get_set_val(expr));
+        let constant: u16 = self.identifier_constant(&name.get_name(), name.get_span());
+        self.emit_constant_op(OpCode::SetProperty, constant, name.get_span());
+    }
+
+    fn visit_super_expr(&mut self, expr: &SuperExpression) {
+        let method: &Token = Expr::get_super_method(expr);
+        let span: Span = method.get_span();
+        self.named_variable(&self.this_string.clone(), span, None);
+        self.named_variable(&self.super_string.clone(), span, None);
+        let constant: u16 = self.identifier_constant(&method.get_name(), span);
+        self.emit_constant_op(OpCode::GetSuper, constant, span);
+    }
+
+    fn visit_unary_expr(&mut self, expr: &UnaryExpression) {
+        let span: Span = Expr::get_unary_operator(expr).get_span();
+        self.compile_expr(Expr::get_unary_expr(expr));
+        match Expr::get_unary_op(expr) {
+            TokenType::Minus => self.emit_op(OpCode::Negate, span),
+            TokenType::Bang => self.emit_op(OpCode::Not, span),
+            // There should not be any other types of operations in Unary Expressions
+            _ => unreachable!(),
+        }
+    }
+}
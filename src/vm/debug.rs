@@ -0,0 +1,138 @@
+use crate::vm::chunk::{Chunk, OpCode};
+use crate::vm::object::{Heap, Object, VmValue};
+
+// Lists every instruction of a function, then of the functions it defines, e.g.
+//
+// == <script> ==
+// 0000    1 OP_CONSTANT         0 '1.5'
+// 0003    | OP_PRINT
+pub fn disassemble_function(heap: &Heap, function: VmValue) -> String {
+    let function = match function {
+        VmValue::Obj(obj) => heap.function(obj),
+        _ => unreachable!(),
+    };
+    let name: String = match &function.name {
+        Some(name) => name.clone(),
+        None => "<script>".to_string(),
+    };
+    let chunk: &Chunk = &function.chunk;
+
+    let mut out: String = format!("== {} ==\n", name);
+    let mut offset: usize = 0;
+    while offset < chunk.len() {
+        let (text, next) = disassemble_instruction(heap, chunk, offset);
+        out += &text;
+        out += "\n";
+        offset = next;
+    }
+
+    for index in 0..chunk.constant_count() {
+        let constant: VmValue = chunk.get_constant(index);
+        if let VmValue::Obj(obj) = constant {
+            if let Object::Function(_) = heap.get(obj) {
+                out += &disassemble_function(heap, constant);
+            }
+        }
+    }
+    out
+}
+
+// Describes the instruction at `offset`, also returning where the next one starts
+pub fn disassemble_instruction(heap: &Heap, chunk: &Chunk, offset: usize) -> (String, usize) {
+    let line: i32 = chunk.get_span(offset).get_line();
+    let same_line: bool = offset > 0 && chunk.get_span(offset - 1).get_line() == line;
+    let prefix: String = if same_line {
+        format!("{:04}    | ", offset)
+    } else {
+        format!("{:04} {:4} ", offset, line)
+    };
+
+    let op: OpCode = OpCode::from_byte(chunk.get_byte(offset));
+    let (text, next): (String, usize) = match op {
+        OpCode::Constant
+        | OpCode::GetGlobal
+        | OpCode::DefineGlobal
+        | OpCode::SetGlobal
+        | OpCode::GetProperty
+        | OpCode::SetProperty
+        | OpCode::GetSuper
+        | OpCode::Class
+        | OpCode::Method => {
+            let index: usize = read_short(chunk, offset + 1);
+            let constant: String = heap.stringify(chunk.get_constant(index));
+            (
+                format!("{:<16} {:4} '{}'", op.name(), index, constant),
+                offset + 3,
+            )
+        }
+        OpCode::GetLocal
+        | OpCode::SetLocal
+        | OpCode::GetUpvalue
+        | OpCode::SetUpvalue
+        | OpCode::Call => {
+            let operand: u8 = chunk.get_byte(offset + 1);
+            (format!("{:<16} {:4}", op.name(), operand), offset + 2)
+        }
+        OpCode::Jump | OpCode::JumpIfFalse | OpCode::Loop => {
+            let jump: usize = read_short(chunk, offset + 1);
+            let target: usize = if op == OpCode::Loop {
+                offset + 3 - jump
+            } else {
+                offset + 3 + jump
+            };
+            (
+                format!("{:<16} {:4} -> {}", op.name(), offset, target),
+                offset + 3,
+            )
+        }
+        OpCode::Invoke | OpCode::SuperInvoke => {
+            let index: usize = read_short(chunk, offset + 1);
+            let arg_count: u8 = chunk.get_byte(offset + 3);
+            let constant: String = heap.stringify(chunk.get_constant(index));
+            (
+                format!(
+                    "{:<16} ({} args) {:4} '{}'",
+                    op.name(),
+                    arg_count,
+                    index,
+                    constant
+                ),
+                offset + 4,
+            )
+        }
+        OpCode::Closure => {
+            let index: usize = read_short(chunk, offset + 1);
+            let function: VmValue = chunk.get_constant(index);
+            let mut text: String =
+                format!("{:<16} {:4} {}", op.name(), index, heap.stringify(function));
+            // Each captured variable is described by a pair of bytes
+            let upvalue_count: usize = match function {
+                VmValue::Obj(obj) => heap.function(obj).upvalue_count,
+                _ => unreachable!(),
+            };
+            let mut next: usize = offset + 3;
+            for _ in 0..upvalue_count {
+                let kind: &str = if chunk.get_byte(next) == 1 {
+                    "local"
+                } else {
+                    "upvalue"
+                };
+                text += &format!(
+                    "\n{:04}    |                     {} {}",
+                    next,
+                    kind,
+                    chunk.get_byte(next + 1)
+                );
+                next += 2;
+            }
+            (text, next)
+        }
+        _ => (op.name().to_string(), offset + 1),
+    };
+    (prefix + &text, next)
+}
+
+// Two byte operand, high byte first
+fn read_short(chunk: &Chunk, offset: usize) -> usize {
+    ((chunk.get_byte(offset) as usize) << 8) | chunk.get_byte(offset + 1) as usize
+}
@@ -0,0 +1,747 @@
+pub mod chunk;
+pub mod compiler;
+pub mod debug;
+pub mod object;
+
+use crate::callable::{clock, NativeFn};
+use crate::diagnostic::{Diagnostic, ErrorKind};
+use crate::error::LoxError;
+use crate::intern::Symbol;
+use crate::stmt::Stmt;
+use crate::token::Span;
+use crate::value::{Value, VmObject};
+use crate::vm::chunk::{Chunk, OpCode};
+use crate::vm::compiler::Compiler;
+use crate::vm::object::{
+    BoundMethod, Class, Closure, Heap, Instance, Native, ObjRef, Object, Upvalue, VmValue,
+};
+use crate::Rlox;
+use std::collections::HashMap;
+use std::io::{self, stdout, Write};
+use std::rc::Rc;
+
+// Deepest call nesting before reporting a stack overflow, the same as the tree-walker's limit
+const FRAMES_MAX: usize = 1024;
+
+struct CallFrame {
+    closure: ObjRef,
+    chunk: Rc<Chunk>,
+    ip: usize,
+    // Stack index of the frame's slot zero
+    slots: usize,
+}
+
+// Stack based virtual machine running compiled bytecode
+pub struct Vm {
+    heap: Heap,
+    stack: Vec<VmValue>,
+    frames: Vec<CallFrame>,
+    globals: HashMap<Symbol, VmValue>,
+    // Upvalues still pointing into the stack, ordered by slot
+    open_upvalues: Vec<ObjRef>,
+    // Where `print` writes to, along with any debugging output
+    out: Box<dyn Write>,
+    // Print each function's bytecode after compiling it
+    disassemble: bool,
+    // Print the stack and the next instruction before executing it
+    trace: bool,
+    // Collect garbage on every allocation, to shake out missing roots
+    stress_gc: bool,
+    // Looked up on every instantiation, so only interned once
+    init_string: Symbol,
+}
+
+impl Vm {
+    pub fn new() -> Vm {
+        let mut vm = Vm {
+            heap: Heap::new(),
+            stack: Vec::new(),
+            frames: Vec::new(),
+            globals: HashMap::new(),
+            open_upvalues: Vec::new(),
+            out: Box::new(stdout()),
+            disassemble: false,
+            trace: false,
+            stress_gc: false,
+            init_string: Symbol::intern("init"),
+        };
+        vm.define_native("clock", 0, Rc::new(clock));
+        vm
+    }
+
+    pub fn set_output(&mut self, out: Box<dyn Write>) {
+        self.out = out;
+    }
+
+    pub fn set_disassemble(&mut self, disassemble: bool) {
+        self.disassemble = disassemble;
+    }
+
+    pub fn set_trace(&mut self, trace: bool) {
+        self.trace = trace;
+    }
+
+    pub fn set_stress_gc(&mut self, stress_gc: bool) {
+        self.stress_gc = stress_gc;
+    }
+
+    // Only values with a counterpart outside the VM can be read back
+    pub fn get_global(&self, name: &str) -> Option<Value> {
+        self.globals
+            .get(&Symbol::intern(name))
+            .map(|val| self.to_value(*val))
+    }
+
+    pub fn set_global(&mut self, name: &str, val: Value) -> Result<(), String> {
+        let val: VmValue = self.alloc_value(val)?;
+        self.globals.insert(Symbol::intern(name), val);
+        Ok(())
+    }
+
+    pub fn global_names(&self) -> Vec<Symbol> {
+        self.globals.keys().cloned().collect()
+    }
+
+    pub fn define_native(&mut self, name: &str, arity: usize, function: Rc<NativeFn>) {
+        let native: ObjRef = self.alloc(Object::Native(Native {
+            name: name.to_string(),
+            arity,
+            function,
+        }));
+        self.globals
+            .insert(Symbol::intern(name), VmValue::Obj(native));
+    }
+
+    pub fn compile(&mut self, rlox: &mut Rlox, stmts: &[Stmt]) -> Result<ObjRef, LoxError> {
+        let function: ObjRef = Compiler::new(rlox, &mut self.heap).compile(stmts);
+        if self.disassemble && !rlox.had_error {
+            let text: String = debug::disassemble_function(&self.heap, VmValue::Obj(function));
+            // Nothing is running yet, so there is no span to blame
+            write!(self.out, "{}", text)
+                .and_then(|()| self.out.flush())
+                .map_err(|error| output_error(Span::default(), error))?;
+        }
+        Ok(function)
+    }
+
+    // Runs a compiled program, returning the value of its trailing expression if it has one
+    pub fn interpret(&mut self, function: ObjRef) -> Result<Value, LoxError> {
+        // Keep the function reachable while its closure is allocated
+        self.push(VmValue::Obj(function));
+        let closure: ObjRef = self.alloc(Object::Closure(Closure {
+            function,
+            upvalues: Vec::new(),
+        }));
+        self.pop();
+        self.push(VmValue::Obj(closure));
+        self.call(closure, 0)?;
+        let result: Result<VmValue, LoxError> = self.run();
+        // Hand over any trace output still buffered, an error stopping the program comes first
+        let flushed: io::Result<()> = self.out.flush();
+        let result: VmValue = result?;
+        flushed.map_err(|error| output_error(Span::default(), error))?;
+        Ok(self.to_value(result))
+    }
+
+    fn run(&mut self) -> Result<VmValue, LoxError> {
+        loop {
+            if self.trace {
+                self.trace_instruction()?;
+            }
+            match OpCode::from_byte(self.read_byte()) {
+                OpCode::Constant => {
+                    let constant: VmValue = self.read_constant();
+                    self.push(constant);
+                }
+                OpCode::Nil => self.push(VmValue::Nil),
+                OpCode::True => self.push(VmValue::Bool(true)),
+                OpCode::False => self.push(VmValue::Bool(false)),
+                OpCode::Pop => {
+                    self.pop();
+                }
+                OpCode::GetLocal => {
+                    let slot: usize = self.frame().slots + self.read_byte() as usize;
+                    self.push(self.stack[slot]);
+                }
+                OpCode::SetLocal => {
+                    let slot: usize = self.frame().slots + self.read_byte() as usize;
+                    self.stack[slot] = self.peek(0);
+                }
+                OpCode::GetGlobal => {
+                    let name: Symbol = self.read_string();
+                    match self.globals.get(&name) {
+                        Some(val) => self.push(*val),
+                        None => return Err(self.error(format!("Undefined variable '{}'", name))),
+                    }
+                }
+                OpCode::DefineGlobal => {
+                    let name: Symbol = self.read_string();
+                    self.globals.insert(name, self.peek(0));
+                    self.pop();
+                }
+                OpCode::SetGlobal => {
+                    let name: Symbol = self.read_string();
+                    // Assignment never creates a global
+                    if !self.globals.contains_key(&name) {
+                        return Err(self.error(format!("Undefined variable '{}'", name)));
+                    }
+                    self.globals.insert(name, self.peek(0));
+                }
+                OpCode::GetUpvalue => {
+                    let upvalue: ObjRef = self.read_upvalue();
+                    let val: VmValue = match self.heap.get(upvalue) {
+                        Object::Upvalue(Upvalue::Open(slot)) => self.stack[*slot],
+                        Object::Upvalue(Upvalue::Closed(val)) => *val,
+                        _ => unreachable!(),
+                    };
+                    self.push(val);
+                }
+                OpCode::SetUpvalue => {
+                    let upvalue: ObjRef = self.read_upvalue();
+                    let val: VmValue = self.peek(0);
+                    match self.heap.get_mut(upvalue) {
+                        Object::Upvalue(Upvalue::Open(slot)) => self.stack[*slot] = val,
+                        Object::Upvalue(Upvalue::Closed(closed)) => *closed = val,
+                        _ => unreachable!(),
+                    }
+                }
+                OpCode::GetProperty => {
+                    let name: Symbol = self.read_string();
+                    let instance: &Instance = match self.as_instance(self.peek(0)) {
+                        Some(instance) => instance,
+                        None => {
+                            return Err(self.error("Only instances have properties".to_string()))
+                        }
+                    };
+                    // Fields shadow methods
+                    if let Some(val) = instance.fields.get(&name) {
+                        let val: VmValue = *val;
+                        self.pop();
+                        self.push(val);
+                    } else {
+                        let class: ObjRef = instance.class;
+                        self.bind_method(class, &name)?;
+                    }
+                }
+                OpCode::SetProperty => {
+                    let name: Symbol = self.read_string();
+                    let val: VmValue = self.peek(0);
+                    match self.peek(1) {
+                        VmValue::Obj(obj) if matches!(self.heap.get(obj), Object::Instance(_)) => {
+                            if let Object::Instance(instance) = self.heap.get_mut(obj) {
+                                instance.fields.insert(name, val);
+                            }
+                        }
+                        _ => return Err(self.error("Only instances have fields".to_string())),
+                    }
+                    // Leave the assigned value in place of the instance
+                    self.pop();
+                    self.pop();
+                    self.push(val);
+                }
+                OpCode::GetSuper => {
+                    let name: Symbol = self.read_string();
+                    let superclass: ObjRef = self.pop_obj();
+                    self.bind_method(superclass, &name)?;
+                }
+                OpCode::Equal => {
+                    let right: VmValue = self.pop();
+                    let left: VmValue = self.pop();
+                    // Strings are interned, so every value is equal only to itself
+                    self.push(VmValue::Bool(left == right));
+                }
+                OpCode::Greater => self.binary_op(|l, r| VmValue::Bool(l > r))?,
+                OpCode::Less => self.binary_op(|l, r| VmValue::Bool(l < r))?,
+                OpCode::Add => {
+                    let right: VmValue = self.peek(0);
+                    let left: VmValue = self.peek(1);
+                    match (left, right) {
+                        (VmValue::Number(left), VmValue::Number(right)) => {
+                            self.pop();
+                            self.pop();
+                            self.push(VmValue::Number(left + right));
+                        }
+                        (VmValue::Obj(left), VmValue::Obj(right)) => {
+                            let joined: String = match (self.heap.get(left), self.heap.get(right)) {
+                                (Object::String(left), Object::String(right)) => {
+                                    left.to_string() + right
+                                }
+                                _ => return Err(self.add_error()),
+                            };
+                            self.pop();
+                            self.pop();
+                            let val: VmValue = self.alloc_string(Symbol::from(joined));
+                            self.push(val);
+                        }
+                        _ => return Err(self.add_error()),
+                    }
+                }
+                OpCode::Subtract => self.binary_op(|l, r| VmValue::Number(l - r))?,
+                OpCode::Multiply => self.binary_op(|l, r| VmValue::Number(l * r))?,
+                OpCode::Divide => self.binary_op(|l, r| VmValue::Number(l / r))?,
+                OpCode::Not => {
+                    let val: VmValue = self.pop();
+                    self.push(VmValue::Bool(is_falsey(val)));
+                }
+                OpCode::Negate => match self.peek(0) {
+                    VmValue::Number(num) => {
+                        self.pop();
+                        self.push(VmValue::Number(-num));
+                    }
+                    _ => return Err(self.error("Operand must be a number".to_string())),
+                },
+                OpCode::Print => {
+                    let val: VmValue = self.pop();
+                    let text: String = self.heap.stringify(val);
+                    // A closed pipe or full disk stops the program like any other runtime error
+                    if let Err(error) =
+                        writeln!(self.out, "{}", text).and_then(|()| self.out.flush())
+                    {
+                        return Err(self.write_error(error));
+                    }
+                }
+                OpCode::Jump => {
+                    let offset: usize = self.read_short();
+                    self.frame_mut().ip += offset;
+                }
+                OpCode::JumpIfFalse => {
+                    let offset: usize = self.read_short();
+                    if is_falsey(self.peek(0)) {
+                        self.frame_mut().ip += offset;
+                    }
+                }
+                OpCode::Loop => {
+                    let offset: usize = self.read_short();
+                    self.frame_mut().ip -= offset;
+                }
+                OpCode::Call => {
+                    let arg_count: usize = self.read_byte() as usize;
+                    self.call_value(self.peek(arg_count), arg_count)?;
+                }
+                OpCode::Invoke => {
+                    let name: Symbol = self.read_string();
+                    let arg_count: usize = self.read_byte() as usize;
+                    self.invoke(&name, arg_count)?;
+                }
+                OpCode::SuperInvoke => {
+                    let name: Symbol = self.read_string();
+                    let arg_count: usize = self.read_byte() as usize;
+                    let superclass: ObjRef = self.pop_obj();
+                    self.invoke_from_class(superclass, &name, arg_count)?;
+                }
+                OpCode::Closure => {
+                    let function: ObjRef = match self.read_constant() {
+                        VmValue::Obj(function) => function,
+                        _ => unreachable!(),
+                    };
+                    let upvalue_count: usize = self.heap.function(function).upvalue_count;
+                    let mut upvalues: Vec<ObjRef> = Vec::with_capacity(upvalue_count);
+                    for _ in 0..upvalue_count {
+                        let is_local: bool = self.read_byte() == 1;
+                        let index: usize = self.read_byte() as usize;
+                        if is_local {
+                            let slot: usize = self.frame().slots + index;
+                            upvalues.push(self.capture_upvalue(slot));
+                        } else {
+                            let enclosing: ObjRef = self.frame().closure;
+                            upvalues.push(self.heap.closure(enclosing).upvalues[index]);
+                        }
+                    }
+                    let closure: ObjRef =
+                        self.alloc(Object::Closure(Closure { function, upvalues }));
+                    self.push(VmValue::Obj(closure));
+                }
+                OpCode::CloseUpvalue => {
+                    self.close_upvalues(self.stack.len() - 1);
+                    self.pop();
+                }
+                OpCode::Return => {
+                    let result: VmValue = self.pop();
+                    let frame: CallFrame = self.frames.pop().expect("Return outside a frame");
+                    self.close_upvalues(frame.slots);
+                    self.stack.truncate(frame.slots);
+                    if self.frames.is_empty() {
+                        return Ok(result);
+                    }
+                    self.push(result);
+                }
+                OpCode::Class => {
+                    let name: Symbol = self.read_string();
+                    let class: ObjRef = self.alloc(Object::Class(Class {
+                        name,
+                        methods: HashMap::new(),
+                    }));
+                    self.push(VmValue::Obj(class));
+                }
+                OpCode::Inherit => {
+                    let superclass: ObjRef = match self.peek(1) {
+                        VmValue::Obj(obj) if matches!(self.heap.get(obj), Object::Class(_)) => obj,
+                        _ => return Err(self.error("Superclass must be a class".to_string())),
+                    };
+                    // Copy the methods down, subclass methods are added afterwards and override them
+                    let methods: HashMap<Symbol, ObjRef> = match self.heap.get(superclass) {
+                        Object::Class(class) => class.methods.clone(),
+                        _ => unreachable!(),
+                    };
+                    let subclass: ObjRef = self.pop_obj();
+                    self.heap.class_mut(subclass).methods.extend(methods);
+                }
+                OpCode::Method => {
+                    let name: Symbol = self.read_string();
+                    let method: ObjRef = self.pop_obj();
+                    let class: ObjRef = match self.peek(0) {
+                        VmValue::Obj(class) => class,
+                        _ => unreachable!(),
+                    };
+                    self.heap.class_mut(class).methods.insert(name, method);
+                }
+            }
+        }
+    }
+
+    fn trace_instruction(&mut self) -> Result<(), LoxError> {
+        let mut stack: String = " ".repeat(10);
+        for val in &self.stack {
+            stack += &format!("[ {} ]", self.heap.stringify(*val));
+        }
+        let frame: &CallFrame = self.frame();
+        let (instruction, _) = debug::disassemble_instruction(&self.heap, &frame.chunk, frame.ip);
+        writeln!(self.out, "{}\n{}", stack, instruction).map_err(|error| self.write_error(error))
+    }
+
+    fn frame(&self) -> &CallFrame {
+        self.frames.last().expect("VM must be inside a frame")
+    }
+
+    fn frame_mut(&mut self) -> &mut CallFrame {
+        self.frames.last_mut().expect("VM must be inside a frame")
+    }
+
+    fn read_byte(&mut self) -> u8 {
+        let frame: &mut CallFrame = self.frame_mut();
+        let byte: u8 = frame.chunk.get_byte(frame.ip);
+        frame.ip += 1;
+        byte
+    }
+
+    fn read_short(&mut self) -> usize {
+        let high: usize = self.read_byte() as usize;
+        let low: usize = self.read_byte() as usize;
+        (high << 8) | low
+    }
+
+    fn read_constant(&mut self) -> VmValue {
+        let index: usize = self.read_short();
+        self.frame().chunk.get_constant(index)
+    }
+
+    fn read_string(&mut self) -> Symbol {
+        match self.read_constant() {
+            VmValue::Obj(obj) => self.heap.string(obj),
+            _ => unreachable!(),
+        }
+    }
+
+    fn read_upvalue(&mut self) -> ObjRef {
+        let index: usize = self.read_byte() as usize;
+        self.heap.closure(self.frame().closure).upvalues[index]
+    }
+
+    fn push(&mut self, val: VmValue) {
+        self.stack.push(val);
+    }
+
+    fn pop(&mut self) -> VmValue {
+        self.stack.pop().expect("VM stack underflow")
+    }
+
+    // Pops a value the compiler guarantees is an object
+    fn pop_obj(&mut self) -> ObjRef {
+        match self.pop() {
+            VmValue::Obj(obj) => obj,
+            _ => unreachable!(),
+        }
+    }
+
+    fn peek(&self, distance: usize) -> VmValue {
+        self.stack[self.stack.len() - 1 - distance]
+    }
+
+    fn as_instance(&self, val: VmValue) -> Option<&Instance> {
+        match val {
+            VmValue::Obj(obj) => match self.heap.get(obj) {
+                Object::Instance(instance) => Some(instance),
+                _ => None,
+            },
+            _ => None,
+        }
+    }
+
+    fn binary_op(&mut self, op: fn(f64, f64) -> VmValue) -> Result<(), LoxError> {
+        match (self.peek(1), self.peek(0)) {
+            (VmValue::Number(left), VmValue::Number(right)) => {
+                self.pop();
+                self.pop();
+                self.push(op(left, right));
+                Ok(())
+            }
+            _ => Err(self.error("Operands must be numbers".to_string())),
+        }
+    }
+
+    fn add_error(&mut self) -> LoxError {
+        self.error("Operands must be either numbers or strings".to_string())
+    }
+
+    fn call_value(&mut self, callee: VmValue, arg_count: usize) -> Result<(), LoxError> {
+        let obj: ObjRef = match callee {
+            VmValue::Obj(obj) => obj,
+            _ => return Err(self.error("Can only call functions and classes".to_string())),
+        };
+        let callee_slot: usize = self.stack.len() - arg_count - 1;
+        match self.heap.get(obj) {
+            Object::Closure(_) => self.call(obj, arg_count),
+            Object::BoundMethod(bound) => {
+                let (receiver, method) = (bound.receiver, bound.method);
+                // The receiver becomes slot zero, i.e. `this`
+                self.stack[callee_slot] = receiver;
+                self.call(method, arg_count)
+            }
+            Object::Class(class) => {
+                let initializer: Option<ObjRef> = class.methods.get(&self.init_string).copied();
+                let instance: ObjRef = self.alloc(Object::Instance(Instance {
+                    class: obj,
+                    fields: HashMap::new(),
+                }));
+                self.stack[callee_slot] = VmValue::Obj(instance);
+                match initializer {
+                    Some(initializer) => self.call(initializer, arg_count),
+                    None if arg_count != 0 => Err(self.arity_error(0, arg_count)),
+                    None => Ok(()),
+                }
+            }
+            Object::Native(native) => {
+                if arg_count != native.arity {
+                    return Err(self.arity_error(native.arity, arg_count));
+                }
+                let function: Rc<NativeFn> = Rc::clone(&native.function);
+                let args: Vec<Value> = self.stack[callee_slot + 1..]
+                    .iter()
+                    .map(|val| self.to_value(*val))
+                    .collect();
+                let result: Value = function(args).map_err(|message| self.error(message))?;
+                let result: VmValue = match self.alloc_value(result) {
+                    Ok(result) => result,
+                    Err(_) => {
+                        return Err(self.error(
+                            "Native functions only return numbers, strings, booleans and nil"
+                                .to_string(),
+                        ))
+                    }
+                };
+                self.stack.truncate(callee_slot);
+                self.push(result);
+                Ok(())
+            }
+            _ => Err(self.error("Can only call functions and classes".to_string())),
+        }
+    }
+
+    fn call(&mut self, closure: ObjRef, arg_count: usize) -> Result<(), LoxError> {
+        let function = self.heap.function(self.heap.closure(closure).function);
+        if arg_count != function.arity {
+            return Err(self.arity_error(function.arity, arg_count));
+        }
+        // The script's own frame doesn't count as a call
+        if self.frames.len() == FRAMES_MAX + 1 {
+            return Err(self.error("Stack overflow".to_string()));
+        }
+        let chunk: Rc<Chunk> = Rc::clone(&function.chunk);
+        self.frames.push(CallFrame {
+            closure,
+            chunk,
+            ip: 0,
+            slots: self.stack.len() - arg_count - 1,
+        });
+        Ok(())
+    }
+
+    fn invoke(&mut self, name: &Symbol, arg_count: usize) -> Result<(), LoxError> {
+        let instance: &Instance = match self.as_instance(self.peek(arg_count)) {
+            Some(instance) => instance,
+            None => return Err(self.error("Only instances have properties".to_string())),
+        };
+        // A field holding a function is called like any other value
+        if let Some(field) = instance.fields.get(name) {
+            let field: VmValue = *field;
+            let callee_slot: usize = self.stack.len() - arg_count - 1;
+            self.stack[callee_slot] = field;
+            return self.call_value(field, arg_count);
+        }
+        let class: ObjRef = instance.class;
+        self.invoke_from_class(class, name, arg_count)
+    }
+
+    fn invoke_from_class(
+        &mut self,
+        class: ObjRef,
+        name: &Symbol,
+        arg_count: usize,
+    ) -> Result<(), LoxError> {
+        match self.find_method(class, name) {
+            Some(method) => self.call(method, arg_count),
+            None => Err(self.error(format!("Undefined property '{}'", name))),
+        }
+    }
+
+    fn find_method(&self, class: ObjRef, name: &Symbol) -> Option<ObjRef> {
+        match self.heap.get(class) {
+            Object::Class(class) => class.methods.get(name).copied(),
+            _ => unreachable!(),
+        }
+    }
+
+    // Replaces the receiver on top of the stack with one of its class's methods bound to it
+    fn bind_method(&mut self, class: ObjRef, name: &Symbol) -> Result<(), LoxError> {
+        let method: ObjRef = match self.find_method(class, name) {
+            Some(method) => method,
+            None => return Err(self.error(format!("Undefined property '{}'", name))),
+        };
+        let bound: ObjRef = self.alloc(Object::BoundMethod(BoundMethod {
+            receiver: self.peek(0),
+            method,
+        }));
+        self.pop();
+        self.push(VmValue::Obj(bound));
+        Ok(())
+    }
+
+    // May collect first, so anything the new object refers to must already be reachable
+    fn alloc(&mut self, object: Object) -> ObjRef {
+        if self.stress_gc || self.heap.should_collect() {
+            self.collect_garbage();
+        }
+        self.heap.alloc(object)
+    }
+
+    fn alloc_string(&mut self, str: Symbol) -> VmValue {
+        if let Some(existing) = self.heap.find_string(&str) {
+            return VmValue::Obj(existing);
+        }
+        VmValue::Obj(self.alloc(Object::String(str)))
+    }
+
+    fn collect_garbage(&mut self) {
+        for val in &self.stack {
+            self.heap.mark_value(*val);
+        }
+        for frame in &self.frames {
+            self.heap.mark_object(frame.closure);
+        }
+        for upvalue in &self.open_upvalues {
+            self.heap.mark_object(*upvalue);
+        }
+        for val in self.globals.values() {
+            self.heap.mark_value(*val);
+        }
+        self.heap.collect();
+    }
+
+    fn capture_upvalue(&mut self, slot: usize) -> ObjRef {
+        // Closures capturing the same variable share one upvalue
+        for upvalue in &self.open_upvalues {
+            if let Object::Upvalue(Upvalue::Open(open)) = self.heap.get(*upvalue) {
+                if *open == slot {
+                    return *upvalue;
+                }
+            }
+        }
+        let upvalue: ObjRef = self.alloc(Object::Upvalue(Upvalue::Open(slot)));
+        self.open_upvalues.push(upvalue);
+        upvalue
+    }
+
+    // Moves every variable at or above `last` off the stack and into its upvalue
+    fn close_upvalues(&mut self, last: usize) {
+        let mut index: usize = 0;
+        while index < self.open_upvalues.len() {
+            let upvalue: ObjRef = self.open_upvalues[index];
+            match self.heap.get_mut(upvalue) {
+                Object::Upvalue(state) => match *state {
+                    Upvalue::Open(slot) if slot >= last => {
+                        *state = Upvalue::Closed(self.stack[slot]);
+                        self.open_upvalues.swap_remove(index);
+                    }
+                    _ => index += 1,
+                },
+                _ => unreachable!(),
+            }
+        }
+    }
+
+    // Unwinds the VM and reports `message` at the current instruction
+    fn error(&mut self, message: String) -> LoxError {
+        let frame: &CallFrame = self.frame();
+        let span = frame.chunk.get_span(frame.ip - 1);
+        // Closures that escaped still need the variables they captured
+        self.close_upvalues(0);
+        self.stack.clear();
+        self.frames.clear();
+        self.open_upvalues.clear();
+        LoxError::Runtime(Diagnostic::new(
+            ErrorKind::Runtime,
+            span,
+            "".to_string(),
+            message,
+        ))
+    }
+
+    fn write_error(&mut self, error: io::Error) -> LoxError {
+        self.error(format!("Unable to write output: {}", error))
+    }
+
+    fn arity_error(&mut self, arity: usize, arg_count: usize) -> LoxError {
+        self.error(format!(
+            "Expected {} arguments but got {}",
+            arity, arg_count
+        ))
+    }
+
+    fn to_value(&self, val: VmValue) -> Value {
+        match val {
+            VmValue::Nil => Value::Nil,
+            VmValue::Bool(b) => Value::Bool(b),
+            VmValue::Number(num) => Value::Number(num),
+            VmValue::Obj(obj) => match self.heap.get(obj) {
+                Object::String(str) => Value::String(str.clone()),
+                _ => Value::VmObject(VmObject::new(self.heap.stringify(val))),
+            },
+        }
+    }
+
+    fn alloc_value(&mut self, val: Value) -> Result<VmValue, String> {
+        match val {
+            Value::Nil => Ok(VmValue::Nil),
+            Value::Bool(b) => Ok(VmValue::Bool(b)),
+            Value::Number(num) => Ok(VmValue::Number(num)),
+            Value::String(str) => Ok(self.alloc_string(str)),
+            // Functions, classes and instances of the tree-walker can't cross over, and a VM
+            // object handed out is only a description of it
+            _ => Err(format!("The VM can't hold the value {}", val)),
+        }
+    }
+}
+
+// For output written while no instruction is running
+fn output_error(span: Span, error: io::Error) -> LoxError {
+    LoxError::Runtime(Diagnostic::new(
+        ErrorKind::Runtime,
+        span,
+        "".to_string(),
+        format!("Unable to write output: {}", error),
+    ))
+}
+
+fn is_falsey(val: VmValue) -> bool {
+    matches!(val, VmValue::Nil | VmValue::Bool(false))
+}
@@ -0,0 +1,326 @@
+use crate::callable::NativeFn;
+use crate::intern::Symbol;
+use crate::vm::chunk::Chunk;
+use std::collections::HashMap;
+use std::rc::Rc;
+
+// Handle to an object living in the VM's heap
+#[derive(Clone, Copy, Debug, PartialEq, Eq, Hash)]
+pub struct ObjRef(usize);
+
+// A value on the VM's stack. Small and copyable, anything bigger lives in the heap
+#[derive(Clone, Copy, Debug, PartialEq)]
+pub enum VmValue {
+    Nil,
+    Bool(bool),
+    Number(f64),
+    Obj(ObjRef),
+}
+
+pub enum Object {
+    String(Symbol),
+    Function(Function),
+    Native(Native),
+    Closure(Closure),
+    Upvalue(Upvalue),
+    Class(Class),
+    Instance(Instance),
+    BoundMethod(BoundMethod),
+}
+
+impl Object {
+    // Rough number of bytes owned by the object, used to pace collections
+    fn size(&self) -> usize {
+        let contents: usize = match self {
+            Object::String(str) => str.len(),
+            Object::Function(function) => {
+                function.chunk.len() + function.chunk.constant_count() * size_of::<VmValue>()
+            }
+            Object::Closure(closure) => closure.upvalues.len() * size_of::<ObjRef>(),
+            Object::Class(class) => class.methods.len() * size_of::<(String, ObjRef)>(),
+            Object::Instance(instance) => instance.fields.len() * size_of::<(String, VmValue)>(),
+            Object::Native(_) | Object::Upvalue(_) | Object::BoundMethod(_) => 0,
+        };
+        size_of::<Object>() + contents
+    }
+}
+
+pub struct Function {
+    pub name: Option<String>,
+    pub arity: usize,
+    pub upvalue_count: usize,
+    // Shared so call frames can hold on to it without borrowing the heap
+    pub chunk: Rc<Chunk>,
+}
+
+pub struct Native {
+    pub name: String,
+    pub arity: usize,
+    pub function: Rc<NativeFn>,
+}
+
+pub struct Closure {
+    pub function: ObjRef,
+    pub upvalues: Vec<ObjRef>,
+}
+
+// A variable captured by a closure. It points into the stack until the variable goes
+// out of scope, then holds on to the value itself
+pub enum Upvalue {
+    Open(usize),
+    Closed(VmValue),
+}
+
+pub struct Class {
+    pub name: Symbol,
+    pub methods: HashMap<Symbol, ObjRef>,
+}
+
+pub struct Instance {
+    pub class: ObjRef,
+    pub fields: HashMap<Symbol, VmValue>,
+}
+
+pub struct BoundMethod {
+    pub receiver: VmValue,
+    pub method: ObjRef,
+}
+
+// Collections start once this many bytes are allocated, the threshold then adapts to the live heap
+const INITIAL_GC_THRESHOLD: usize = 1024 * 1024;
+const GC_GROWTH_FACTOR: usize = 2;
+
+struct Slot {
+    object: Object,
+    marked: bool,
+    // Estimated size when allocated, subtracted again when freed
+    size: usize,
+}
+
+// Owns every object the VM allocates. Objects stay put until a collection finds them
+// unreachable, after which their slot is reused
+pub struct Heap {
+    slots: Vec<Option<Slot>>,
+    free: Vec<usize>,
+    bytes_allocated: usize,
+    next_gc: usize,
+    // Objects reached but not yet traced during a collection
+    gray: Vec<ObjRef>,
+    // The one string object for each piece of text. Weak, entries go when their string is freed
+    strings: HashMap<Symbol, ObjRef>,
+}
+
+impl Default for Heap {
+    fn default() -> Heap {
+        Heap::new()
+    }
+}
+
+impl Heap {
+    pub fn new() -> Heap {
+        Heap {
+            slots: Vec::new(),
+            free: Vec::new(),
+            bytes_allocated: 0,
+            next_gc: INITIAL_GC_THRESHOLD,
+            gray: Vec::new(),
+            strings: HashMap::new(),
+        }
+    }
+
+    // Never collects, callers decide when that is safe with `should_collect`. Strings
+    // must be looked up with `find_string` first so each text is only allocated once
+    pub fn alloc(&mut self, object: Object) -> ObjRef {
+        let size: usize = object.size();
+        let string: Option<Symbol> = match &object {
+            Object::String(str) => Some(str.clone()),
+            _ => None,
+        };
+        self.bytes_allocated += size;
+        let slot = Slot {
+            object,
+            marked: false,
+            size,
+        };
+        let obj: ObjRef = match self.free.pop() {
+            Some(index) => {
+                self.slots[index] = Some(slot);
+                ObjRef(index)
+            }
+            None => {
+                self.slots.push(Some(slot));
+                ObjRef(self.slots.len() - 1)
+            }
+        };
+        if let Some(str) = string {
+            self.strings.insert(str, obj);
+        }
+        obj
+    }
+
+    pub fn find_string(&self, str: &Symbol) -> Option<ObjRef> {
+        self.strings.get(str).copied()
+    }
+
+    pub fn get(&self, obj: ObjRef) -> &Object {
+        match &self.slots[obj.0] {
+            Some(slot) => &slot.object,
+            None => panic!("Object {} was used after being freed", obj.0),
+        }
+    }
+
+    pub fn get_mut(&mut self, obj: ObjRef) -> &mut Object {
+        match &mut self.slots[obj.0] {
+            Some(slot) => &mut slot.object,
+            None => panic!("Object {} was used after being freed", obj.0),
+        }
+    }
+
+    pub fn should_collect(&self) -> bool {
+        self.bytes_allocated > self.next_gc
+    }
+
+    pub fn mark_value(&mut self, val: VmValue) {
+        if let VmValue::Obj(obj) = val {
+            self.mark_object(obj);
+        }
+    }
+
+    pub fn mark_object(&mut self, obj: ObjRef) {
+        if let Some(slot) = &mut self.slots[obj.0] {
+            if !slot.marked {
+                slot.marked = true;
+                self.gray.push(obj);
+            }
+        }
+    }
+
+    // Marks everything reachable from the roots marked so far, then frees the rest
+    pub fn collect(&mut self) {
+        while let Some(obj) = self.gray.pop() {
+            self.blacken(obj);
+        }
+        self.sweep();
+        self.next_gc = (self.bytes_allocated * GC_GROWTH_FACTOR).max(INITIAL_GC_THRESHOLD);
+    }
+
+    fn blacken(&mut self, obj: ObjRef) {
+        let mut children: Vec<VmValue> = Vec::new();
+        match self.get(obj) {
+            Object::String(_) | Object::Native(_) => {}
+            Object::Function(function) => {
+                for index in 0..function.chunk.constant_count() {
+                    children.push(function.chunk.get_constant(index));
+                }
+            }
+            Object::Closure(closure) => {
+                children.push(VmValue::Obj(closure.function));
+                children.extend(
+                    closure
+                        .upvalues
+                        .iter()
+                        .map(|upvalue| VmValue::Obj(*upvalue)),
+                );
+            }
+            Object::Upvalue(Upvalue::Open(_)) => {}
+            Object::Upvalue(Upvalue::Closed(val)) => children.push(*val),
+            Object::Class(class) => {
+                children.extend(class.methods.values().map(|method| VmValue::Obj(*method)));
+            }
+            Object::Instance(instance) => {
+                children.push(VmValue::Obj(instance.class));
+                children.extend(instance.fields.values().copied());
+            }
+            Object::BoundMethod(bound) => {
+                children.push(bound.receiver);
+                children.push(VmValue::Obj(bound.method));
+            }
+        }
+        for child in children {
+            self.mark_value(child);
+        }
+    }
+
+    fn sweep(&mut self) {
+        for (index, entry) in self.slots.iter_mut().enumerate() {
+            match entry {
+                Some(slot) if slot.marked => slot.marked = false,
+                Some(slot) => {
+                    self.bytes_allocated -= slot.size;
+                    if let Object::String(str) = &slot.object {
+                        self.strings.remove(str);
+                    }
+                    *entry = None;
+                    self.free.push(index);
+                }
+                None => {}
+            }
+        }
+    }
+
+    pub fn alloc_string(&mut self, str: Symbol) -> VmValue {
+        match self.find_string(&str) {
+            Some(existing) => VmValue::Obj(existing),
+            None => VmValue::Obj(self.alloc(Object::String(str))),
+        }
+    }
+
+    // The accessors below are only used where the compiler guarantees the object's type
+
+    pub fn function(&self, obj: ObjRef) -> &Function {
+        match self.get(obj) {
+            Object::Function(function) => function,
+            _ => unreachable!(),
+        }
+    }
+
+    pub fn closure(&self, obj: ObjRef) -> &Closure {
+        match self.get(obj) {
+            Object::Closure(closure) => closure,
+            _ => unreachable!(),
+        }
+    }
+
+    pub fn string(&self, obj: ObjRef) -> Symbol {
+        match self.get(obj) {
+            Object::String(str) => str.clone(),
+            _ => unreachable!(),
+        }
+    }
+
+    pub fn class_mut(&mut self, obj: ObjRef) -> &mut Class {
+        match self.get_mut(obj) {
+            Object::Class(class) => class,
+            _ => unreachable!(),
+        }
+    }
+
+    pub fn stringify(&self, val: VmValue) -> String {
+        match val {
+            VmValue::Nil => "nil".to_string(),
+            VmValue::Bool(b) => b.to_string(),
+            VmValue::Number(num) => {
+                let mut num_str = num.to_string();
+                if num_str.ends_with(".0") {
+                    num_str.truncate(num_str.len() - 2);
+                }
+                num_str
+            }
+            VmValue::Obj(obj) => match self.get(obj) {
+                Object::String(str) => str.to_string(),
+                Object::Function(function) => match &function.name {
+                    Some(name) => format!("<fn {}>", name),
+                    None => "<script>".to_string(),
+                },
+                Object::Native(native) => format!("<fn {}>", native.name),
+                Object::Closure(closure) => self.stringify(VmValue::Obj(closure.function)),
+                Object::Upvalue(_) => "upvalue".to_string(),
+                Object::Class(class) => class.name.to_string(),
+                Object::Instance(instance) => {
+                    format!("{} instance", self.stringify(VmValue::Obj(instance.class)))
+                }
+                Object::BoundMethod(bound) => self.stringify(VmValue::Obj(bound.method)),
+            },
+        }
+    }
+}
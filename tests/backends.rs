@@ -0,0 +1,99 @@
+use rlox::{Backend, Lox, LoxError};
+use std::cell::RefCell;
+use std::fs;
+use std::io::{self, Write};
+use std::path::Path;
+use std::rc::Rc;
+use std::thread;
+
+// The tree-walker recurses natively for every Lox call, so deep recursion needs a big stack
+const STACK_SIZE: usize = 256 * 1024 * 1024;
+const MAX_STACK: usize = STACK_SIZE / 2;
+
+struct Capture(Rc<RefCell<Vec<u8>>>);
+
+impl Write for Capture {
+    fn write(&mut self, buf: &[u8]) -> io::Result<usize> {
+        self.0.borrow_mut().extend_from_slice(buf);
+        Ok(buf.len())
+    }
+
+    fn flush(&mut self) -> io::Result<()> {
+        Ok(())
+    }
+}
+
+// Everything a script printed, followed by its value or the error it stopped with
+fn run(backend: Backend, source: &str) -> String {
+    let out = Rc::new(RefCell::new(Vec::new()));
+    let mut lox = Lox::new();
+    lox.set_backend(backend);
+    lox.set_max_stack(MAX_STACK);
+    lox.set_output(Box::new(Capture(Rc::clone(&out))));
+    let result = match lox.eval(source) {
+        Ok(val) => format!("=> {}", val),
+        Err(error @ LoxError::Compile(_)) => format!("compile error: {}", error),
+        Err(error @ LoxError::Runtime(_)) => format!("runtime error: {}", error),
+    };
+    let printed = String::from_utf8(out.take()).expect("Output must be UTF-8");
+    printed + &result
+}
+
+fn assert_same(name: &str, source: &str) {
+    let tree = run(Backend::Tree, source);
+    let vm = run(Backend::Vm, source);
+    assert_eq!(tree, vm, "backends disagree on {}", name);
+}
+
+// Both backends have to produce exactly the output committed next to the script
+fn assert_expected(path: &Path) {
+    let source = fs::read_to_string(path).expect("Unable to read a test script!");
+    let expected = fs::read_to_string(path.with_extension("expected"))
+        .expect("Every test script needs a .expected file next to it!");
+    for backend in [Backend::Tree, Backend::Vm] {
+        assert_eq!(
+            run(backend, &source),
+            expected.trim_end_matches('\n'),
+            "{:?} backend got unexpected output for {}",
+            backend,
+            path.display()
+        );
+    }
+}
+
+fn with_big_stack(test: fn()) {
+    thread::Builder::new()
+        .stack_size(STACK_SIZE)
+        .spawn(test)
+        .expect("Unable to start the test thread!")
+        .join()
+        .unwrap_or_else(|panic| std::panic::resume_unwind(panic));
+}
+
+#[test]
+fn backends_match_expected_output() {
+    with_big_stack(|| {
+        let dir = Path::new(env!("CARGO_MANIFEST_DIR")).join("tests/lox");
+        let mut paths: Vec<_> = fs::read_dir(dir)
+            .expect("Unable to read the test corpus!")
+            .map(|entry| entry.expect("Unable to read the test corpus!").path())
+            .filter(|path| path.extension().is_some_and(|ext| ext == "lox"))
+            .collect();
+        paths.sort();
+        assert!(!paths.is_empty());
+        for path in paths {
+            assert_expected(&path);
+        }
+    });
+}
+
+#[test]
+fn backends_agree_on_many_constants() {
+    let mut source = String::from("var s = 0;\n");
+    for i in 0..1000 {
+        source += &format!("s = s + {};\n", i % 300);
+    }
+    source += "s;";
+    assert_same("many constants", &source);
+    assert_eq!(run(Backend::Vm, &source), "=> 139500");
+}
@@ -0,0 +1,208 @@
+use std::fs;
+use std::iter::Peekable;
+use std::path::{Path, PathBuf};
+use std::process::{Command, Output};
+use std::str::Chars;
+
+fn script(name: &str) -> PathBuf {
+    Path::new(env!("CARGO_MANIFEST_DIR"))
+        .join("tests/lox")
+        .join(name)
+}
+
+fn rlox(args: &[&str]) -> Output {
+    Command::new(env!("CARGO_BIN_EXE_rlox"))
+        .args(args)
+        .output()
+        .expect("Unable to run rlox!")
+}
+
+fn status(args: &[&str]) -> Option<i32> {
+    rlox(args).status.code()
+}
+
+#[test]
+fn successful_script_exits_with_zero() {
+    let output = rlox(&[script("value.lox").to_str().unwrap()]);
+    assert_eq!(output.status.code(), Some(0));
+    assert!(output.stderr.is_empty());
+}
+
+#[test]
+fn usage_errors_exit_with_64() {
+    let value = script("value.lox");
+    let value = value.to_str().unwrap();
+    assert_eq!(status(&["--bogus"]), Some(64));
+    assert_eq!(status(&[value, value]), Some(64));
+    // Debugging flags only apply to the VM
+    assert_eq!(status(&["--trace", value]), Some(64));
+}
+
+#[test]
+fn compile_errors_exit_with_65() {
+    let output = rlox(&[script("compile_error.lox").to_str().unwrap()]);
+    assert_eq!(output.status.code(), Some(65));
+    assert!(!output.stderr.is_empty());
+}
+
+#[test]
+fn missing_script_exits_with_66() {
+    let missing = script("missing.lox");
+    assert_eq!(status(&[missing.to_str().unwrap()]), Some(66));
+}
+
+#[test]
+fn runtime_errors_exit_with_70() {
+    let runtime_error = script("runtime_error.lox");
+    for backend in ["--backend=tree", "--backend=vm"] {
+        let output = rlox(&[backend, runtime_error.to_str().unwrap()]);
+        assert_eq!(output.status.code(), Some(70), "{}", backend);
+        assert_eq!(output.stdout, b"before\n", "{}", backend);
+    }
+}
+
+#[test]
+fn strict_mode_rejects_undeclared_assignments_at_resolve_time() {
+    let undeclared = script("undeclared.lox");
+    let output = rlox(&["--strict", undeclared.to_str().unwrap()]);
+    assert_eq!(output.status.code(), Some(65));
+    assert!(output.stdout.is_empty());
+    let stderr = String::from_utf8(output.stderr).expect("Errors must be UTF-8");
+    assert!(stderr.starts_with("error[E0003]: Undefined variable 'missing'\n"));
+
+    let output = rlox(&[undeclared.to_str().unwrap()]);
+    assert_eq!(output.status.code(), Some(70));
+    assert_eq!(output.stdout, b"before\n");
+}
+
+// A field of a diagnostic printed by --error-format=json
+#[derive(Debug, PartialEq)]
+enum Json {
+    String(String),
+    Number(f64),
+}
+
+fn expect(chars: &mut Peekable<Chars>, expected: char) {
+    assert_eq!(chars.next(), Some(expected), "malformed JSON");
+}
+
+fn parse_string(chars: &mut Peekable<Chars>) -> String {
+    expect(chars, '"');
+    let mut out = String::new();
+    loop {
+        match chars.next().expect("unterminated JSON string") {
+            '"' => return out,
+            '\\' => match chars.next().expect("unterminated JSON escape") {
+                'n' => out.push('\n'),
+                'r' => out.push('\r'),
+                't' => out.push('\t'),
+                'u' => {
+                    let hex: String = chars.by_ref().take(4).collect();
+                    let code = u32::from_str_radix(&hex, 16).expect("bad JSON escape");
+                    out.push(char::from_u32(code).expect("bad JSON escape"));
+                }
+                c => out.push(c),
+            },
+            c => {
+                assert!(c >= ' ', "unescaped control character in JSON string");
+                out.push(c);
+            }
+        }
+    }
+}
+
+// Parses one flat JSON object, keeping its fields in order
+fn parse_object(line: &str) -> Vec<(String, Json)> {
+    let mut chars = line.chars().peekable();
+    let mut fields = Vec::new();
+    expect(&mut chars, '{');
+    loop {
+        let key = parse_string(&mut chars);
+        expect(&mut chars, ':');
+        let val = if chars.peek() == Some(&'"') {
+            Json::String(parse_string(&mut chars))
+        } else {
+            let mut number = String::new();
+            while let Some(c) = chars.next_if(|c| c.is_ascii_digit() || *c == '-' || *c == '.') {
+                number.push(c);
+            }
+            Json::Number(number.parse().expect("bad JSON number"))
+        };
+        fields.push((key, val));
+        match chars.next() {
+            Some(',') => continue,
+            Some('}') => break,
+            c => panic!("malformed JSON, got {:?}", c),
+        }
+    }
+    assert_eq!(chars.next(), None, "trailing text after JSON object");
+    fields
+}
+
+fn diagnostic(
+    kind: &str,
+    code: &str,
+    file: &str,
+    at: (f64, f64),
+    lexeme: &str,
+    message: &str,
+) -> Vec<(String, Json)> {
+    let text = |key: &str, val: &str| (key.to_string(), Json::String(val.to_string()));
+    vec![
+        text("kind", kind),
+        text("code", code),
+        text("file", file),
+        ("line".to_string(), Json::Number(at.0)),
+        ("column".to_string(), Json::Number(at.1)),
+        text("lexeme", lexeme),
+        text("message", message),
+    ]
+}
+
+#[test]
+fn json_errors_are_one_object_per_diagnostic() {
+    let path = script("syntax_errors.lox");
+    let file = path.to_str().unwrap();
+    let output = rlox(&["--error-format=json", file]);
+    assert_eq!(output.status.code(), Some(65));
+    let stderr = String::from_utf8(output.stderr).expect("Errors must be UTF-8");
+    let objects: Vec<_> = stderr.lines().map(parse_object).collect();
+    let parse = |at, lexeme, message| diagnostic("parse", "E0002", file, at, lexeme, message);
+    assert_eq!(
+        objects,
+        [
+            diagnostic(
+                "scan",
+                "E0001",
+                file,
+                (4.0, 7.0),
+                "\"unterminated\n",
+                "Unterminated String!"
+            ),
+            parse((1.0, 10.0), ";", "Expect expression"),
+            parse((2.0, 5.0), "\"tab\there\"", "Expect variable name"),
+            parse((3.0, 8.0), "{", "Expect parameter name"),
+            parse((5.0, 1.0), "", "Expect expression"),
+        ]
+    );
+}
+
+// Compares the debugging output for a script with a jump, a closure and an upvalue
+fn assert_debug_output(flag: &str, extension: &str) {
+    let path = Path::new(env!("CARGO_MANIFEST_DIR")).join("tests/debug/adder.lox");
+    let output = rlox(&["--backend=vm", flag, path.to_str().unwrap()]);
+    assert_eq!(output.status.code(), Some(0));
+    let expected = fs::read_to_string(path.with_extension(extension))
+        .expect("Unable to read the expected debugging output!");
+    assert_eq!(String::from_utf8(output.stdout).unwrap(), expected);
+}
+
+#[test]
+fn disassembly_matches_expected_output() {
+    assert_debug_output("--disassemble", "disassemble");
+}
+
+#[test]
+fn trace_matches_expected_output() {
+    assert_debug_output("--trace", "trace");
+}
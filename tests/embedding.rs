@@ -0,0 +1,320 @@
+use rlox::{Backend, Lox, LoxError, Value};
+use std::cell::RefCell;
+use std::io::{self, BufWriter, Write};
+use std::rc::Rc;
+use std::thread;
+
+// Holds on to what was written until it is flushed, fails every flush if `broken`
+struct Buffered {
+    pending: Vec<u8>,
+    flushed: Rc<RefCell<Vec<u8>>>,
+    broken: bool,
+}
+
+impl Write for Buffered {
+    fn write(&mut self, buf: &[u8]) -> io::Result<usize> {
+        self.pending.extend_from_slice(buf);
+        Ok(buf.len())
+    }
+
+    fn flush(&mut self) -> io::Result<()> {
+        if self.broken {
+            return Err(io::Error::other("disk full"));
+        }
+        self.flushed.borrow_mut().append(&mut self.pending);
+        Ok(())
+    }
+}
+
+fn buffered(backend: Backend, broken: bool) -> (Lox, Rc<RefCell<Vec<u8>>>) {
+    let flushed = Rc::new(RefCell::new(Vec::new()));
+    let mut lox = Lox::new();
+    lox.set_backend(backend);
+    lox.set_output(Box::new(Buffered {
+        pending: Vec::new(),
+        flushed: Rc::clone(&flushed),
+        broken,
+    }));
+    (lox, flushed)
+}
+
+fn vm() -> Lox {
+    let mut lox = Lox::new();
+    lox.set_backend(Backend::Vm);
+    lox
+}
+
+fn runtime_message(result: Result<Value, LoxError>) -> String {
+    match result {
+        Err(LoxError::Runtime(diagnostic)) => diagnostic.get_message(),
+        result => panic!(
+            "expected a runtime error, got {:?}",
+            result.map(|val| val.to_string())
+        ),
+    }
+}
+
+#[test]
+fn vm_closures_outlive_a_runtime_error() {
+    let mut lox = vm();
+    let source = "var f; { var a = 1; var b = 2; var c = 3; var x = 4; \
+                  fun g() { return x; } f = g; nil + 1; }";
+    assert!(lox.eval(source).is_err());
+    assert_eq!(lox.eval("f();").ok(), Some(Value::Number(4.0)));
+}
+
+// Runs on a thread of the default size, not the big one the command line uses
+#[test]
+fn deep_recursion_fits_a_default_thread() {
+    thread::spawn(|| {
+        let mut lox = Lox::new();
+        let result = lox.eval("fun f() { return f(); } f();");
+        assert_eq!(runtime_message(result), "Stack overflow");
+        let result = lox.eval(
+            "class A { m(a, b, c) { var d = a + b; { var e = d * c; return this.m(d, e, c); } } } \
+             A().m(1, 2, 3);",
+        );
+        assert_eq!(runtime_message(result), "Stack overflow");
+        // Still usable afterwards
+        assert_eq!(lox.eval("1 + 2;").ok(), Some(Value::Number(3.0)));
+    })
+    .join()
+    .unwrap_or_else(|panic| std::panic::resume_unwind(panic));
+}
+
+#[test]
+fn print_output_is_flushed_by_both_backends() {
+    for backend in [Backend::Tree, Backend::Vm] {
+        let (mut lox, flushed) = buffered(backend, false);
+        assert!(lox.eval("print 1; print \"two\";").is_ok());
+        assert_eq!(flushed.take(), b"1\ntwo\n", "{:?}", backend);
+    }
+}
+
+#[test]
+fn failed_output_is_a_runtime_error_on_both_backends() {
+    for backend in [Backend::Tree, Backend::Vm] {
+        let (mut lox, _) = buffered(backend, true);
+        let message = runtime_message(lox.eval("print 1;"));
+        assert_eq!(
+            message, "Unable to write output: disk full",
+            "{:?}",
+            backend
+        );
+    }
+}
+
+#[test]
+fn vm_trace_output_is_flushed() {
+    let flushed = Rc::new(RefCell::new(Vec::new()));
+    let mut lox = vm();
+    lox.set_trace(true);
+    lox.set_output(Box::new(BufWriter::new(Buffered {
+        pending: Vec::new(),
+        flushed: Rc::clone(&flushed),
+        broken: false,
+    })));
+    assert!(lox.eval("1 + 2;").is_ok());
+    let trace = String::from_utf8(flushed.take()).expect("Output must be UTF-8");
+    assert!(trace.contains("OP_ADD"), "{}", trace);
+}
+
+#[test]
+fn rejected_globals_leave_both_backends_alone() {
+    let mut tree = Lox::new();
+    assert!(tree.eval("fun f() {}").is_ok());
+    let function = tree.get_global("f").expect("f must be defined");
+
+    let mut lox = vm();
+    assert!(lox.set_global("g", Value::Number(1.0)).is_ok());
+    assert!(lox.set_global("g", function).is_err());
+    assert_eq!(lox.get_global("g"), Some(Value::Number(1.0)));
+    lox.set_backend(Backend::Tree);
+    assert_eq!(lox.get_global("g"), Some(Value::Number(1.0)));
+}
+
+#[test]
+fn errors_in_earlier_evals_render_without_a_snippet() {
+    for backend in [Backend::Tree, Backend::Vm] {
+        let mut lox = Lox::new();
+        lox.set_backend(backend);
+        assert!(lox.eval("fun f() { return nil + 1; }").is_ok());
+        let source = "var unrelated = 1;\nf();";
+        let error = lox.eval(source).expect_err("f must fail");
+        let rendered = error.get_diagnostics()[0].render("<stdin>", source, false);
+        assert!(rendered.starts_with(
+            "error[E0004]: Operands must be either numbers or strings\n --> <stdin>:1:22\n"
+        ));
+        assert!(
+            rendered.ends_with("  = in code from an earlier input\n"),
+            "{}",
+            rendered
+        );
+        assert!(!rendered.contains("unrelated"), "{}", rendered);
+
+        // The current source is still shown for its own errors
+        let error = lox.eval("nil + 1;").expect_err("addition must fail");
+        let rendered = error.get_diagnostics()[0].render("<stdin>", "nil + 1;", false);
+        assert!(
+            rendered.contains("1 | nil + 1;\n  |     ^ at '+'\n"),
+            "{}",
+            rendered
+        );
+    }
+}
+
+fn compile_messages(result: Result<Value, LoxError>) -> Vec<String> {
+    match result {
+        Err(LoxError::Compile(diagnostics)) => diagnostics
+            .iter()
+            .map(|diagnostic| diagnostic.get_message())
+            .collect(),
+        result => panic!(
+            "expected a compile error, got {:?}",
+            result.map(|val| val.to_string())
+        ),
+    }
+}
+
+#[test]
+fn strict_mode_rejects_undeclared_assignments_before_running() {
+    for backend in [Backend::Tree, Backend::Vm] {
+        let (mut lox, flushed) = buffered(backend, false);
+        lox.set_strict(true);
+        let result = lox.eval("print \"ran\"; fun f() { missing = 1; }");
+        assert_eq!(compile_messages(result), ["Undefined variable 'missing'"]);
+        assert!(flushed.take().is_empty(), "{:?}", backend);
+    }
+}
+
+#[test]
+fn strict_mode_accepts_declared_globals() {
+    for backend in [Backend::Tree, Backend::Vm] {
+        let mut lox = Lox::new();
+        lox.set_backend(backend);
+        lox.set_strict(true);
+        // Functions may assign to globals declared after them, or in an earlier eval
+        let result = lox.eval("fun f() { later = 2; } var later = 1; f(); later;");
+        assert_eq!(result.ok(), Some(Value::Number(2.0)), "{:?}", backend);
+        let result = lox.eval("later = 3; later;");
+        assert_eq!(result.ok(), Some(Value::Number(3.0)), "{:?}", backend);
+    }
+}
+
+#[test]
+fn undeclared_variables_are_runtime_errors_without_strict_mode() {
+    for backend in [Backend::Tree, Backend::Vm] {
+        let mut lox = Lox::new();
+        lox.set_backend(backend);
+        let message = runtime_message(lox.eval("missing = 1;"));
+        assert_eq!(message, "Undefined variable 'missing'", "{:?}", backend);
+        let message = runtime_message(lox.eval("print missing;"));
+        assert_eq!(message, "Undefined variable 'missing'", "{:?}", backend);
+    }
+}
+
+fn with_natives(backend: Backend) -> Lox {
+    let mut lox = Lox::new();
+    lox.set_backend(backend);
+    lox.define_native("add", 2, |args| match (&args[0], &args[1]) {
+        (Value::Number(a), Value::Number(b)) => Ok(Value::Number(a + b)),
+        _ => Err("add takes two numbers".to_string()),
+    });
+    lox.define_native("fail", 0, |_| Err("native failed".to_string()));
+    lox
+}
+
+#[test]
+fn natives_are_called_with_their_arguments() {
+    for backend in [Backend::Tree, Backend::Vm] {
+        let mut lox = with_natives(backend);
+        let result = lox.eval("add(1, add(2, 3));");
+        assert_eq!(result.ok(), Some(Value::Number(6.0)), "{:?}", backend);
+    }
+}
+
+#[test]
+fn natives_check_their_arity() {
+    for backend in [Backend::Tree, Backend::Vm] {
+        let mut lox = with_natives(backend);
+        let message = runtime_message(lox.eval("add(1);"));
+        assert_eq!(message, "Expected 2 arguments but got 1", "{:?}", backend);
+        let message = runtime_message(lox.eval("fail(1, 2, 3);"));
+        assert_eq!(message, "Expected 0 arguments but got 3", "{:?}", backend);
+    }
+}
+
+#[test]
+fn native_errors_become_runtime_errors() {
+    for backend in [Backend::Tree, Backend::Vm] {
+        let mut lox = with_natives(backend);
+        let message = runtime_message(lox.eval("fail();"));
+        assert_eq!(message, "native failed", "{:?}", backend);
+        let message = runtime_message(lox.eval("add(\"1\", 2);"));
+        assert_eq!(message, "add takes two numbers", "{:?}", backend);
+    }
+}
+
+const GLOBALS: &str = "var none = nil; var yes = true; var number = 1.5; var string = \"text\"; \
+                       fun function(a) {} class Class {} var instance = Class();";
+
+#[test]
+fn tree_globals_convert_to_values() {
+    let mut lox = Lox::new();
+    assert!(lox.eval(GLOBALS).is_ok());
+    let global = |name: &str| lox.get_global(name).expect("global must be defined");
+    assert_eq!(global("none"), Value::Nil);
+    assert_eq!(global("yes"), Value::Bool(true));
+    assert_eq!(global("number"), Value::Number(1.5));
+    assert_eq!(global("string").to_string(), "text");
+    assert!(matches!(global("string"), Value::String(_)));
+    match global("function") {
+        Value::Callable(function) => {
+            assert_eq!(function.name(), "function");
+            assert_eq!(function.arity(), 1);
+        }
+        val => panic!("expected a callable, got {}", val),
+    }
+    assert!(matches!(global("Class"), Value::Class(_)));
+    assert_eq!(global("Class").to_string(), "Class");
+    assert!(matches!(global("instance"), Value::Instance(_)));
+    assert_eq!(global("instance").to_string(), "Class instance");
+    assert_eq!(lox.get_global("undefined"), None);
+}
+
+#[test]
+fn vm_globals_convert_to_values() {
+    let mut lox = vm();
+    assert!(lox.eval(GLOBALS).is_ok());
+    let global = |name: &str| lox.get_global(name).expect("global must be defined");
+    assert_eq!(global("none"), Value::Nil);
+    assert_eq!(global("yes"), Value::Bool(true));
+    assert_eq!(global("number"), Value::Number(1.5));
+    assert_eq!(global("string").to_string(), "text");
+    assert!(matches!(global("string"), Value::String(_)));
+    // Heap objects stay in the VM, the host only gets what `print` shows
+    for (name, printed) in [
+        ("function", "<fn function>"),
+        ("Class", "Class"),
+        ("instance", "Class instance"),
+    ] {
+        assert!(matches!(global(name), Value::VmObject(_)), "{}", name);
+        assert_eq!(global(name).to_string(), printed);
+    }
+    assert_eq!(lox.get_global("undefined"), None);
+}
+
+#[test]
+fn vm_natives_only_return_plain_values() {
+    let mut tree = Lox::new();
+    assert!(tree.eval("fun f() {}").is_ok());
+    let function = tree.get_global("f").expect("f must be defined");
+
+    let mut lox = vm();
+    lox.define_native("leak", 0, move |_| Ok(function.clone()));
+    let message = runtime_message(lox.eval("leak();"));
+    assert_eq!(
+        message,
+        "Native functions only return numbers, strings, booleans and nil"
+    );
+}